@@ -1,9 +1,114 @@
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use std::path::PathBuf;
 
 #[derive(Parser, Debug)]
-#[command(version, about, long_about = None)]
+#[command(version, about, long_about = None, args_conflicts_with_subcommands = true)]
 pub struct Args {
-    #[arg(default_value = ".")]
-    pub shortcut_dir: PathBuf
-}
\ No newline at end of file
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// Same as `run --shortcuts <SHORTCUT_DIR>`
+    pub shortcut_dir: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub run: RunArgs,
+}
+
+/// Options of the desktop, also accepted without the `run` subcommand
+#[derive(clap::Args, Debug, Clone, Default)]
+pub struct RunArgs {
+    /// Directories of shortcut files, defaults to the current directory
+    #[arg(long, num_args = 1..)]
+    pub shortcuts: Vec<PathBuf>,
+
+    /// Global config file, defaults to $XDG_CONFIG_HOME/desktop-tui/config.toml
+    #[arg(long)]
+    pub config: Option<PathBuf>,
+
+    /// Reopen the windows of the last session, saved in $XDG_STATE_HOME/desktop-tui/session.toml
+    #[arg(long)]
+    pub restore: bool,
+
+    /// Unix socket of the control API, defaults to $XDG_RUNTIME_DIR/desktop-tui/default.control
+    #[arg(long)]
+    pub control_socket: Option<PathBuf>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Start the desktop, the default when no subcommand is given
+    Run(RunArgs),
+
+    /// Parse the shortcut files of a directory and report their errors, without starting the desktop
+    Validate {
+        #[arg(default_value = ".")]
+        shortcut_dir: PathBuf,
+
+        /// Config file whose [defaults] apply to the shortcuts, defaults to $XDG_CONFIG_HOME/desktop-tui/config.toml
+        #[arg(long)]
+        config: Option<PathBuf>,
+    },
+
+    /// Print the shortcuts in appbar order
+    List {
+        /// Directories of shortcut files, defaults to the current directory
+        #[arg(long, num_args = 1..)]
+        shortcuts: Vec<PathBuf>,
+
+        /// Config file whose [defaults] apply to the shortcuts, defaults to $XDG_CONFIG_HOME/desktop-tui/config.toml
+        #[arg(long)]
+        config: Option<PathBuf>,
+    },
+
+    /// Create a shortcut file by answering questions
+    NewShortcut {
+        /// Directory of the new file
+        #[arg(long, default_value = ".")]
+        dir: PathBuf,
+    },
+
+    /// Show a desktop running in the background, starting it if needed. The detach key binding detaches
+    Attach {
+        /// Name of the desktop
+        #[arg(long, default_value = "default")]
+        name: String,
+
+        /// Arguments of the desktop when it is started, such as the shortcut directory
+        #[arg(last = true)]
+        desktop_args: Vec<String>,
+    },
+
+    /// Detach the client shown by a desktop, which keeps running
+    Detach {
+        /// Name of the desktop
+        #[arg(long, default_value = "default")]
+        name: String,
+    },
+
+    /// List the desktops running in the background
+    Ls,
+
+    /// Send JSON requests to the control API of a desktop and print the responses, read from stdin when none is given
+    Ctl {
+        /// Name of the desktop
+        #[arg(long, default_value = "default")]
+        name: String,
+
+        /// Unix socket of the control API, overrides the name
+        #[arg(long)]
+        socket: Option<PathBuf>,
+
+        /// Requests such as '{"command":"open","shortcut":"Terminal"}', or a command name such as "list"
+        requests: Vec<String>,
+    },
+
+    /// Run a desktop in the background, started by attach
+    #[command(hide = true)]
+    Server {
+        #[arg(long)]
+        name: String,
+
+        #[arg(last = true)]
+        desktop_args: Vec<String>,
+    },
+}
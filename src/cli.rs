@@ -0,0 +1,160 @@
+use crate::shortcut::{load_shortcut_file, missing_dirs, parse_shortcut_dirs, shortcut_files, Shortcut, TaskbarOptions, TerminalOptions, WindowOptions};
+use crate::utils::find_program;
+use anyhow::anyhow;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{stdin, stdout, Write};
+use std::path::{Path, PathBuf};
+use toml::Table;
+
+/// Report the errors of every shortcut file of the directory, with their line and column, fails if there is one
+pub fn validate(shortcut_dir: &Path, defaults: &Table) -> anyhow::Result<()> {
+    let mut names: Vec<(String, PathBuf)> = Vec::new();
+    let mut errors = 0;
+
+    for shortcut_path in shortcut_files(shortcut_dir)? {
+        let shortcut = load_shortcut_file(&shortcut_path, defaults);
+
+        for error in &shortcut.errors {
+            println!("{error}");
+        }
+
+        errors += shortcut.errors.len();
+
+        match names.iter().find(|(name, _)| *name == shortcut.name) {
+            Some((_, other_path)) => {
+                println!("{}: the name \"{}\" is already used by {}", shortcut_path.display(), shortcut.name, other_path.display());
+                errors += 1;
+            }
+            None => {
+                if shortcut.errors.is_empty() {
+                    println!("{}: ok", shortcut_path.display());
+                }
+
+                names.push((shortcut.name, shortcut_path));
+            }
+        }
+    }
+
+    match errors {
+        0 => Ok(()),
+        _ => Err(anyhow!("{errors} error(s) found")),
+    }
+}
+
+/// Print the shortcuts in appbar order, with their command, then the shadowed ones and the missing directories
+pub fn list(shortcut_dirs: &[PathBuf], defaults: &Table) -> anyhow::Result<()> {
+    let (shortcuts, shadowed) = parse_shortcut_dirs(shortcut_dirs, defaults)?;
+
+    for shortcut in shortcuts {
+        let position = shortcut.taskbar.position.map(|position| position.to_string()).unwrap_or("-".to_string());
+        let command = [shortcut.command].into_iter().chain(shortcut.args).collect::<Vec<String>>().join(" ");
+
+        let disabled = match shortcut.errors.is_empty() {
+            true => "",
+            false => "  (disabled)",
+        };
+
+        println!("{position:>3}  {:<20}  {command}{disabled}", shortcut.name);
+    }
+
+    for shadowed_shortcut in shadowed {
+        eprintln!("Shadowed: {}", shadowed_shortcut.describe());
+    }
+
+    for missing_dir in missing_dirs(shortcut_dirs) {
+        eprintln!("Skipped: {missing_dir}");
+    }
+
+    Ok(())
+}
+
+/// Ask for the fields of a shortcut and write it to `<dir>/<name>.toml`
+pub fn new_shortcut(dir: &Path) -> anyhow::Result<()> {
+    let name = prompt("Name")?;
+    if name.is_empty() {
+        return Err(anyhow!("The name is required"));
+    }
+
+    let command = prompt("Command")?;
+    if command.is_empty() {
+        return Err(anyhow!("The command is required"));
+    }
+
+    if find_program(&command).is_none() {
+        println!("Warning: \"{command}\" was not found in $PATH");
+    }
+
+    let args = prompt("Arguments, separated by spaces")?
+        .split_whitespace()
+        .map(str::to_string)
+        .collect();
+
+    let position = prompt("Position on the appbar, empty for the end")?;
+    let position = match position.is_empty() {
+        true => None,
+        false => Some(position.parse::<u32>().map_err(|_| anyhow!("\"{position}\" is not a position"))?),
+    };
+
+    let workspace = prompt("Workspace of the windows, empty for the current one")?;
+    let workspace = match workspace.is_empty() {
+        true => None,
+        false => Some(workspace.parse::<usize>().map_err(|_| anyhow!("\"{workspace}\" is not a workspace number"))?),
+    };
+
+    let shortcut = Shortcut {
+        name: name.clone(),
+        overrides: false,
+        path: PathBuf::new(),
+        errors: Vec::new(),
+        command,
+        args,
+        cwd: None,
+        env: BTreeMap::new(),
+        env_clear: false,
+        shell: false,
+        dialog_dir: None,
+        panes: Vec::new(),
+        taskbar: TaskbarOptions {
+            position,
+            ..TaskbarOptions::default()
+        },
+        window: WindowOptions {
+            workspace,
+            ..WindowOptions::default()
+        },
+        terminal: TerminalOptions::default(),
+    };
+
+    let file_name = name
+        .chars()
+        .map(|character| match character.is_alphanumeric() {
+            true => character.to_ascii_lowercase(),
+            false => '-',
+        })
+        .collect::<String>();
+
+    let shortcut_path = dir.join(format!("{file_name}.toml"));
+
+    if shortcut_path.exists() {
+        return Err(anyhow!("{} already exists", shortcut_path.display()));
+    }
+
+    fs::create_dir_all(dir)?;
+    fs::write(&shortcut_path, toml::to_string(&shortcut)?)?;
+
+    println!("Created {}", shortcut_path.display());
+
+    Ok(())
+}
+
+/// Read a trimmed answer from stdin
+fn prompt(question: &str) -> anyhow::Result<String> {
+    print!("{question}: ");
+    stdout().flush()?;
+
+    let mut answer = String::new();
+    stdin().read_line(&mut answer)?;
+
+    Ok(answer.trim().to_string())
+}
@@ -0,0 +1,64 @@
+use crate::keybindings::KeyBindings;
+use crate::shortcut::check_shortcut_defaults;
+use serde::Deserialize;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use toml::Table;
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Config {
+    #[serde(default)]
+    pub keybindings: KeyBindings,
+    /// Number of virtual workspaces, from 1 to 9
+    #[serde(default = "default_workspaces")]
+    pub workspaces: usize,
+    /// Save the session every given number of seconds, it is always saved on exit
+    #[serde(default)]
+    pub session_autosave: Option<u64>,
+    /// Shortcut fields used when a shortcut file does not set them, tables are inherited key by key
+    #[serde(default)]
+    pub defaults: Table,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            keybindings: KeyBindings::default(),
+            workspaces: default_workspaces(),
+            session_autosave: None,
+            defaults: Table::new(),
+        }
+    }
+}
+
+fn default_workspaces() -> usize {
+    4
+}
+
+/// `$XDG_CONFIG_HOME/desktop-tui/config.toml`, or `~/.config/desktop-tui/config.toml`
+pub fn default_config_path() -> Option<PathBuf> {
+    let config_home = match env::var_os("XDG_CONFIG_HOME") {
+        Some(config_home) if !config_home.is_empty() => PathBuf::from(config_home),
+        _ => PathBuf::from(env::var_os("HOME")?).join(".config"),
+    };
+
+    Some(config_home.join("desktop-tui").join("config.toml"))
+}
+
+/// Parse the given config file, or the default one if it exists
+pub fn parse_config_file(config_path: Option<PathBuf>) -> anyhow::Result<Config> {
+    let config_path = match config_path {
+        Some(config_path) => config_path,
+        None => match default_config_path() {
+            Some(config_path) if config_path.is_file() => config_path,
+            _ => return Ok(Config::default()),
+        }
+    };
+
+    let file_content = fs::read_to_string(&config_path)?;
+    let config = toml::from_str::<Config>(&file_content)?;
+    check_shortcut_defaults(&config.defaults)?;
+
+    Ok(config)
+}
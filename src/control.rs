@@ -0,0 +1,321 @@
+use crate::server::socket_dir;
+use anyhow::anyhow;
+use async_channel::Sender;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+/// Connections served at the same time, the next ones are refused
+const MAX_CONNECTIONS: usize = 8;
+
+/// One JSON object per line, answered by one `Response` line
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum Request {
+    /// Start a new instance of a shortcut
+    Open {
+        shortcut: String,
+    },
+    /// Start a program in a new window, with the window settings and the appbar entry of a shortcut
+    Run {
+        shortcut: String,
+        program: String,
+        #[serde(default)]
+        args: Vec<String>,
+    },
+    /// Type text, then key chords such as "Ctrl+C" or "Enter", in the focused pane of a window
+    SendKeys {
+        window: usize,
+        #[serde(default)]
+        text: String,
+        #[serde(default)]
+        keys: Vec<String>,
+    },
+    Focus {
+        window: usize,
+    },
+    /// Stop the applications of a window and close it
+    Close {
+        window: usize,
+    },
+    /// Run a desktop action by its keybinding name, such as "grid" or "workspace:2"
+    Action {
+        action: String,
+    },
+    /// Running windows
+    List,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Response {
+    pub ok: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Window opened by the request
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub window: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub windows: Option<Vec<WindowInfo>>,
+}
+
+impl Response {
+    pub fn success() -> Self {
+        Self {
+            ok: true,
+            ..Self::default()
+        }
+    }
+
+    pub fn error(error: impl ToString) -> Self {
+        Self {
+            ok: false,
+            error: Some(error.to_string()),
+            ..Self::default()
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct WindowInfo {
+    pub id: usize,
+    pub shortcut: String,
+    pub number: usize,
+    /// Title set by the application of the focused pane
+    pub title: Option<String>,
+    /// Starting at 1
+    pub workspace: usize,
+    pub minimized: bool,
+    pub floating: bool,
+    /// Processes of the panes of every tab
+    pub pids: Vec<u32>,
+}
+
+/// A request waiting for the desktop, which sends the response on `reply`
+pub struct ControlRequest {
+    pub request: Request,
+    pub reply: Sender<Response>,
+}
+
+/// `$XDG_RUNTIME_DIR/desktop-tui/<name>.control`, or `/tmp/desktop-tui-<uid>/<name>.control`.
+/// The name is the one given to `desktop-tui attach`, "default" otherwise
+pub fn default_control_socket_path(name: &str) -> PathBuf {
+    socket_dir().join(format!("{name}.control"))
+}
+
+/// Accept connections on the socket in the background, every request is sent to the desktop
+pub fn serve(socket_path: &Path, requests: Sender<ControlRequest>) -> anyhow::Result<()> {
+    // A socket given by the user is created in its directory as it is
+    let socket_dir = socket_dir();
+    if socket_path.parent() == Some(socket_dir.as_path()) {
+        fs::create_dir_all(&socket_dir)?;
+        fs::set_permissions(&socket_dir, fs::Permissions::from_mode(0o700))?;
+    }
+
+    if UnixStream::connect(socket_path).is_ok() {
+        return Err(anyhow!("{} is used by another desktop", socket_path.display()));
+    }
+
+    // Left by a desktop that did not exit cleanly
+    fs::remove_file(socket_path).ok();
+    let listener = UnixListener::bind(socket_path)?;
+    fs::set_permissions(socket_path, fs::Permissions::from_mode(0o600))?;
+
+    let connections = Arc::new(AtomicUsize::new(0));
+
+    thread::spawn(move || {
+        for mut stream in listener.incoming().flatten() {
+            if connections.fetch_add(1, Ordering::SeqCst) >= MAX_CONNECTIONS {
+                connections.fetch_sub(1, Ordering::SeqCst);
+
+                if let Ok(response) = serde_json::to_string(&Response::error("Too many connections")) {
+                    writeln!(stream, "{response}").ok();
+                }
+                continue;
+            }
+
+            let (requests, connections) = (requests.clone(), connections.clone());
+            thread::spawn(move || {
+                handle_connection(stream, requests);
+                connections.fetch_sub(1, Ordering::SeqCst);
+            });
+        }
+    });
+
+    Ok(())
+}
+
+fn handle_connection(stream: UnixStream, requests: Sender<ControlRequest>) {
+    let Ok(mut writer) = stream.try_clone() else {
+        return;
+    };
+
+    for line in BufReader::new(stream).lines() {
+        let Ok(line) = line else {
+            break;
+        };
+
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let response = match serde_json::from_str::<Request>(&line) {
+            Ok(request) => {
+                let (reply, response) = async_channel::bounded(1);
+
+                if requests.send_blocking(ControlRequest { request, reply }).is_err() {
+                    break;
+                }
+
+                response.recv_blocking().unwrap_or_else(|_| Response::error("The desktop exited"))
+            }
+            Err(error) => Response::error(format!("Invalid request: {error}")),
+        };
+
+        let Ok(response) = serde_json::to_string(&response) else {
+            break;
+        };
+
+        if writeln!(writer, "{response}").is_err() {
+            break;
+        }
+    }
+}
+
+/// Send the requests, or the lines of stdin when there are none, and print the responses.
+/// A request that is not a JSON object is a command name, "list" is `{"command":"list"}`
+pub fn ctl(socket_path: &Path, requests: Vec<String>) -> anyhow::Result<()> {
+    let stream = UnixStream::connect(socket_path)
+        .map_err(|error| anyhow!("Failed to connect to {}: {error}", socket_path.display()))?;
+
+    let requests = match requests.is_empty() {
+        true => std::io::stdin().lines().collect::<Result<Vec<String>, _>>()?,
+        false => requests,
+    };
+
+    let mut writer = stream.try_clone()?;
+    let mut responses = BufReader::new(stream).lines();
+    let mut failed = 0;
+
+    for request in requests {
+        let request = match request.trim_start().starts_with('{') {
+            true => request,
+            false => serde_json::json!({ "command": request.trim() }).to_string(),
+        };
+
+        writeln!(writer, "{request}")?;
+
+        let response = responses.next().ok_or(anyhow!("The desktop closed the connection"))??;
+
+        if !serde_json::from_str::<Response>(&response).is_ok_and(|response| response.ok) {
+            failed += 1;
+        }
+
+        println!("{response}");
+    }
+
+    match failed {
+        0 => Ok(()),
+        _ => Err(anyhow!("{failed} request(s) failed")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::process;
+
+    /// Socket in a new directory left with its default permissions
+    fn test_socket_path(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("desktop-tui-test-{name}-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::set_permissions(&dir, fs::Permissions::from_mode(0o755)).unwrap();
+        dir.join("control.sock")
+    }
+
+    #[test]
+    fn answers_requests() {
+        let socket_path = test_socket_path("answers");
+        let (requests_tx, requests_rx) = async_channel::unbounded();
+
+        serve(&socket_path, requests_tx).unwrap();
+
+        // Stands for the desktop
+        thread::spawn(move || {
+            while let Ok(ControlRequest { request, reply }) = requests_rx.recv_blocking() {
+                let response = match request {
+                    Request::Open { shortcut } if shortcut == "Terminal" => Response {
+                        window: Some(1),
+                        ..Response::success()
+                    },
+                    Request::Open { shortcut } => Response::error(format!("Unknown shortcut \"{shortcut}\"")),
+                    _ => Response::success(),
+                };
+
+                reply.send_blocking(response).ok();
+            }
+        });
+
+        let stream = UnixStream::connect(&socket_path).unwrap();
+        let mut writer = stream.try_clone().unwrap();
+        let mut responses = BufReader::new(stream).lines();
+
+        let mut send = |request: &str| {
+            writeln!(writer, "{request}").unwrap();
+            serde_json::from_str::<Response>(&responses.next().unwrap().unwrap()).unwrap()
+        };
+
+        let response = send(r#"{"command":"open","shortcut":"Terminal"}"#);
+        assert!(response.ok);
+        assert_eq!(response.window, Some(1));
+
+        let response = send(r#"{"command":"open","shortcut":"Editor"}"#);
+        assert!(!response.ok);
+        assert_eq!(response.error.as_deref(), Some("Unknown shortcut \"Editor\""));
+
+        assert!(send(r#"{"command":"send_keys","window":1,"keys":["Ctrl+C"]}"#).ok);
+        assert!(!send(r#"{"command":"unknown"}"#).ok);
+        assert!(!send("not json").ok);
+
+        fs::remove_dir_all(socket_path.parent().unwrap()).ok();
+    }
+
+    #[test]
+    fn keeps_user_directories() {
+        let socket_path = test_socket_path("permissions");
+        let (requests_tx, _requests_rx) = async_channel::unbounded();
+
+        serve(&socket_path, requests_tx).unwrap();
+
+        let dir_mode = fs::metadata(socket_path.parent().unwrap()).unwrap().permissions().mode();
+        let socket_mode = fs::metadata(&socket_path).unwrap().permissions().mode();
+        assert_eq!(dir_mode & 0o777, 0o755);
+        assert_eq!(socket_mode & 0o777, 0o600);
+
+        fs::remove_dir_all(socket_path.parent().unwrap()).ok();
+    }
+
+    #[test]
+    fn refuses_extra_connections() {
+        let socket_path = test_socket_path("connections");
+        let (requests_tx, _requests_rx) = async_channel::unbounded();
+
+        serve(&socket_path, requests_tx).unwrap();
+
+        // Each one is served by its own thread until it is closed
+        let connections: Vec<UnixStream> = (0..MAX_CONNECTIONS).map(|_| UnixStream::connect(&socket_path).unwrap()).collect();
+
+        let refused = UnixStream::connect(&socket_path).unwrap();
+        let response = BufReader::new(refused).lines().next().unwrap().unwrap();
+        assert_eq!(serde_json::from_str::<Response>(&response).unwrap().error.as_deref(), Some("Too many connections"));
+
+        drop(connections);
+        fs::remove_dir_all(socket_path.parent().unwrap()).ok();
+    }
+}
@@ -1,64 +1,1142 @@
+use crate::config::{default_config_path, parse_config_file, Config};
+use crate::control::{serve, ControlRequest, Request, Response, WindowInfo};
 use crate::desktop::mydesktop::Commands;
-use crate::shortcut::Shortcut;
+use crate::keybindings::{parse_key, DesktopAction, KeyBindings};
+use crate::keyboard::to_escape_sequence_vec;
+use crate::panes::{PaneDirection, SplitDirection, PANE_RATIO_STEP};
+use crate::server::{detach, SERVER_ENV};
+use crate::session::{load_session, save_session, Session, WindowState};
+use crate::placeholders::Cancelled;
+use crate::shortcut::{parse_shortcut_dirs, LaunchOptions, PaneOptions, Shortcut};
+use crate::tiling::{Tiling, TilingLayout, MASTER_RATIO_STEP};
 use crate::tui_window::TuiWindow;
-use crate::utils::time_to_string;
+use crate::utils::{find_program, time_to_string};
+use crate::watcher::watch_files;
+use crate::window_switcher::{SwitcherEntry, WindowSwitcher, PREVIEW_HEIGHT, PREVIEW_WIDTH};
 use appcui::prelude::appbar::MenuButton;
-use appcui::prelude::menu::{Command, SingleChoice};
+use appcui::prelude::menu::{Command, SingleChoice, SubMenu};
 use appcui::prelude::*;
 use appcui::ui::appbar::Side;
-use std::collections::HashMap;
-use std::time::Duration;
+use async_channel::{Receiver, Sender};
+use notify::RecommendedWatcher;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+use toml::Table;
+
+/// Time to wait after the last change of a shortcut or config file before reloading them, editors write files in several steps
+const RELOAD_DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// A running window of a shortcut
+#[derive(Clone, Copy, Debug)]
+pub struct AppInstance {
+    /// Never reused, identifies the window in the control API
+    pub id: usize,
+    /// Index of the shortcut
+    pub shortcut: usize,
+    /// Lowest number not used by another instance of the same shortcut, shown in the title
+    pub number: usize,
+    pub window: Handle<TuiWindow>,
+    pub workspace: usize,
+    /// Minimized into the appbar, the window is also hidden while its workspace is not the current one
+    pub minimized: bool,
+    /// Left out of the tiling layouts
+    pub floating: bool,
+}
+
+impl AppInstance {
+    /// Caption in the menu of its shortcut, with its workspace when it is not the current one
+    fn caption(&self, current_workspace: usize) -> String {
+        let mut caption = format!("#{}", self.number);
+
+        if self.workspace != current_workspace {
+            caption += &format!(" - Workspace {}", self.workspace + 1);
+        }
+
+        if self.minimized {
+            caption += " (minimized)";
+        }
+
+        caption
+    }
+
+    /// Visible while its workspace is the current one and it is not minimized
+    fn is_shown(&self, current_workspace: usize) -> bool {
+        self.workspace == current_workspace && !self.minimized
+    }
+
+    /// Arranged by the tiling layout of the current workspace
+    fn is_tiled(&self, current_workspace: usize) -> bool {
+        self.is_shown(current_workspace) && !self.floating
+    }
+}
+
+/// Index of the workspace numbered by a shortcut from 1, the current one when it has none or it does not exist
+fn start_workspace(number: Option<usize>, workspace_count: usize, current_workspace: usize) -> usize {
+    number
+        .map(|number| number.saturating_sub(1))
+        .filter(|workspace| *workspace < workspace_count)
+        .unwrap_or(current_workspace)
+}
+
+/// The shown window whose top-left corner the dropped window was put on, so their title bars are lined up
+///
+/// Passing over a window or stopping elsewhere on it during a move does not group them
+fn drop_target(dropped: usize, windows: &[(AppInstance, Point)], current_workspace: usize) -> Option<usize> {
+    let (_, position) = windows.get(dropped)?;
+
+    windows
+        .iter()
+        .enumerate()
+        .find(|(index, (instance, other_position))| {
+            *index != dropped && instance.is_shown(current_workspace) && other_position == position
+        })
+        .map(|(index, _)| index)
+}
+
+/// Lowest number not used by another instance of the shortcut
+fn free_instance_number(instances: &[AppInstance], shortcut: usize) -> usize {
+    (1..)
+        .find(|number| !instances.iter().any(|instance| instance.shortcut == shortcut && instance.number == *number))
+        .unwrap()
+}
+
+/// Appbar caption of a shortcut, with the number of its minimized instances
+fn app_button_caption(name: &str, instances: &[AppInstance]) -> String {
+    match instances.iter().filter(|instance| instance.minimized).count() {
+        0 => name.to_string(),
+        minimized => format!("{name} ({minimized} minimized)"),
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Workspace {
+    pub tiling: Tiling,
+}
+
+/// Items of an instance in the menu of its shortcut
+#[derive(Clone, Copy, Debug)]
+pub struct InstanceItems {
+    pub submenu: Handle<SubMenu>,
+    pub focus: Handle<Command>,
+    pub minimize: Handle<Command>,
+    pub maximize: Handle<Command>,
+    pub close: Handle<Command>,
+}
+
+/// Appbar menu of a shortcut listing a given number of instances
+#[derive(Clone, Debug)]
+pub struct AppMenu {
+    pub menu: Handle<Menu>,
+    pub button: Handle<MenuButton>,
+    pub instance_items: Vec<InstanceItems>,
+}
 
 #[Desktop(
     events = [AppBarEvents, MenuEvents, DesktopEvents, TimerEvents],
-    overwrite = OnPaint,
-    commands = [Exit, NoArrange, Cascade, Vertical, Horizontal, Grid, AppVisibilityToggle, OpenApp, CloseApp, AppCommand, None]
+    overwrite = OnPaint + OnResize,
+    commands = [Exit, Detach, KillWindow, MinimizeWindow, MaximizeWindow, SwitchWindow, NoArrange, Cascade, Vertical, Horizontal, Grid, MasterStack, Dwindle, Monocle, TileCommand, PaneCommand, Workspace, MoveToWorkspace, OpenApp, InstanceFocus, InstanceMinimize, InstanceMaximize, InstanceClose, AppCommand, None]
 )]
 pub struct MyDesktop {
-    pub arrange_method: Option<desktop::ArrangeWindowsMethod>,
+    pub workspaces: Vec<Workspace>,
+    pub current_workspace: usize,
     pub desktop_menu: Handle<MenuButton>,
     pub arrange_menu: Handle<MenuButton>,
+    pub tilling_menu: Handle<Menu>,
+    pub arrange_choices: Vec<Handle<SingleChoice>>,
+    /// Swap, rotate and resize items of the Tilling menu
+    pub tile_items: Vec<(Handle<Command>, DesktopAction)>,
+    /// Items of the Panes and Tabs submenus of the Desktop menu
+    pub pane_items: Vec<(Handle<Command>, DesktopAction)>,
+    /// Workspace indicator
+    pub workspace_menu_button: Handle<MenuButton>,
+    pub workspace_menu: Handle<Menu>,
+    pub workspace_choices: Vec<Handle<SingleChoice>>,
+    pub move_to_workspace_items: Vec<Handle<Command>>,
     pub separator: Handle<appbar::Separator>,
-    pub app_menues: Vec<Handle<Menu>>,
+    /// Menus of each shortcut indexed by their number of instances.
+    /// Registered menus cannot change their items, so one is built the first time each count is reached and reused afterwards
+    pub app_menus: Vec<Vec<AppMenu>>,
+    /// Button currently shown for each shortcut
     pub app_menu_buttons: Vec<Handle<MenuButton>>,
+    /// Disabled buttons of the shortcuts with errors, the errors are in their tooltip
+    pub broken_shortcut_buttons: Vec<Handle<appbar::Button>>,
     pub shortcuts: Vec<Shortcut>,
-    pub app_windows: HashMap<usize, Handle<TuiWindow>>,
+    pub app_windows: Vec<AppInstance>,
+    /// Open windows, the most recently focused first
+    pub window_mru: Vec<Handle<TuiWindow>>,
     pub time_label: Handle<appbar::Label>,
+    pub key_bindings: KeyBindings,
+    /// Actions triggered by prefix sequences typed in the windows
+    pub action_tx: Sender<DesktopAction>,
+    pub action_rx: Receiver<DesktopAction>,
+    /// Reopen the windows of the last session on start
+    pub restore: bool,
+    pub session_autosave: Option<Duration>,
+    pub last_session_save: Instant,
+    /// Problems to show to the user, title and message
+    pub notifications: Vec<(String, String)>,
+    pub control_socket: PathBuf,
+    /// Requests received on the control socket
+    pub control_tx: Sender<ControlRequest>,
+    pub control_rx: Receiver<ControlRequest>,
+    pub next_window_id: usize,
+    /// Watched and reloaded when their files change
+    pub shortcut_dirs: Vec<PathBuf>,
+    /// Config file given on the command line, the default one otherwise
+    pub config_path: Option<PathBuf>,
+    /// `[defaults]` of the config, inherited by the shortcuts
+    pub shortcut_defaults: Table,
+    pub file_watcher: Option<RecommendedWatcher>,
+    pub file_changes_tx: Sender<()>,
+    pub file_changes_rx: Receiver<()>,
+    /// Last change of a watched file, not reloaded yet
+    pub changed_at: Option<Instant>,
 }
 
 impl MyDesktop {
-    pub fn new(shortcuts: Vec<Shortcut>) -> Self {
+    pub fn new(shortcuts: Vec<Shortcut>, shortcut_dirs: Vec<PathBuf>, config: Config, config_path: Option<PathBuf>, restore: bool, control_socket: PathBuf) -> Self {
+        let (action_tx, action_rx) = async_channel::unbounded();
+        let (control_tx, control_rx) = async_channel::unbounded();
+        // One pending change is enough to reload
+        let (file_changes_tx, file_changes_rx) = async_channel::bounded(1);
+
         Self {
             base: Desktop::new(),
-            arrange_method: None,
+            workspaces: vec![Workspace::default(); config.workspaces.clamp(1, 9)],
+            current_workspace: 0,
             desktop_menu: Handle::None,
             separator: Handle::None,
             arrange_menu: Handle::None,
-            app_menues: vec![Handle::None; shortcuts.len()],
+            tilling_menu: Handle::None,
+            arrange_choices: Vec::new(),
+            tile_items: Vec::new(),
+            pane_items: Vec::new(),
+            workspace_menu_button: Handle::None,
+            workspace_menu: Handle::None,
+            workspace_choices: Vec::new(),
+            move_to_workspace_items: Vec::new(),
+            app_menus: vec![Vec::new(); shortcuts.len()],
             app_menu_buttons: vec![Handle::None; shortcuts.len()],
-            app_windows: HashMap::new(),
+            broken_shortcut_buttons: Vec::new(),
+            app_windows: Vec::new(),
+            window_mru: Vec::new(),
             time_label: Handle::None,
+            key_bindings: config.keybindings,
+            action_tx,
+            action_rx,
+            restore,
+            session_autosave: config.session_autosave.map(Duration::from_secs),
+            last_session_save: Instant::now(),
+            notifications: Vec::new(),
+            control_socket,
+            control_tx,
+            control_rx,
+            next_window_id: 1,
+            shortcut_dirs,
+            config_path,
+            shortcut_defaults: config.defaults,
+            file_watcher: None,
+            file_changes_tx,
+            file_changes_rx,
+            changed_at: None,
             shortcuts,
         }
     }
-    
-    pub fn create_window(&mut self, index: usize, command: String, args: Vec<String>) -> anyhow::Result<()> {
+
+    pub fn create_window(&mut self, index: usize, command: String, args: Vec<String>, launch: LaunchOptions, panes: Vec<PaneOptions>) -> anyhow::Result<Handle<TuiWindow>> {
+        if !self.shortcuts[index].errors.is_empty() {
+            return Err(anyhow::anyhow!(self.shortcuts[index].errors.join("\n")));
+        }
+
         let app_name = self.shortcuts[index].name.clone();
         let window = self.shortcuts[index].window.clone();
         let terminal = self.shortcuts[index].terminal.clone();
 
+        let workspace = start_workspace(window.workspace, self.workspaces.len(), self.current_workspace);
+
+        let number = free_instance_number(&self.app_windows, index);
+
         let window = TuiWindow::new(
-            &app_name,
+            &format!("{app_name} #{number}"),
             command,
             args,
+            launch,
+            panes,
             window,
             terminal,
+            self.key_bindings.clone(),
+            self.action_tx.clone(),
         )?;
 
         let win_handle = self.add_window(window);
-        self.app_windows.insert(index, win_handle);
+        self.next_window_id += 1;
+        self.app_windows.push(AppInstance {
+            id: self.next_window_id - 1,
+            shortcut: index,
+            number,
+            window: win_handle,
+            workspace,
+            minimized: false,
+            floating: false,
+        });
+
+        if workspace != self.current_workspace {
+            self.apply_visibility();
+            self.arrange_workspace();
+        }
+
+        self.update_app_menu(index);
+
+        Ok(win_handle)
+    }
+
+    /// Start a new instance of the shortcut
+    pub fn open_app(&mut self, index: usize) -> anyhow::Result<Handle<TuiWindow>> {
+        let command = self.shortcuts[index].command.clone();
+        let args = self.shortcuts[index].args.clone();
+        let launch = self.shortcuts[index].launch_options();
+        let panes = self.shortcuts[index].panes.clone();
+        self.create_window(index, command, args, launch, panes)
+    }
+
+    /// Instances of the shortcut, by number
+    fn instances(&self, index: usize) -> Vec<AppInstance> {
+        let mut instances: Vec<AppInstance> = self.app_windows
+            .iter()
+            .filter(|instance| instance.shortcut == index)
+            .copied()
+            .collect();
+
+        instances.sort_by_key(|instance| instance.number);
+        instances
+    }
+
+    fn instance_of_window(&self, win_handle: Handle<TuiWindow>) -> Option<AppInstance> {
+        self.app_windows
+            .iter()
+            .find(|instance| instance.window == win_handle)
+            .copied()
+    }
+
+    /// Instance of the focused window
+    fn active_app(&self) -> Option<AppInstance> {
+        let active_window = self.active_window_handle()?;
+
+        self.app_windows
+            .iter()
+            .find(|instance| instance.window == active_window)
+            .copied()
+    }
+
+    /// Instance whose Focus, Minimize, Maximize or Close item was selected
+    fn instance_of_item(&self, item: Handle<Command>) -> Option<AppInstance> {
+        for (index, app_menus) in self.app_menus.iter().enumerate() {
+            let instances = self.instances(index);
+
+            let Some(app_menu) = app_menus.get(instances.len()) else {
+                continue;
+            };
+
+            let slot = app_menu.instance_items
+                .iter()
+                .position(|items| [items.focus, items.minimize, items.maximize, items.close].contains(&item));
+
+            if let Some(slot) = slot {
+                return Some(instances[slot]);
+            }
+        }
+
+        None
+    }
+
+    /// Stop the application of the instance and close its window
+    fn close_instance(&mut self, instance: AppInstance) {
+        if let Some(window) = self.window_mut(instance.window) {
+            window.close_command();
+        }
+
+        self.app_windows.retain(|app_instance| app_instance.window != instance.window);
+        self.update_app_menu(instance.shortcut);
+    }
+
+    /// Show and focus the window of the instance, switching to its workspace, or minimize it and focus the previous window
+    fn set_instance_visible(&mut self, instance: AppInstance, visible: bool) {
+        if let Some(app_instance) = self.app_windows.iter_mut().find(|app_instance| app_instance.window == instance.window) {
+            app_instance.minimized = !visible;
+        }
+
+        if visible && instance.workspace != self.current_workspace {
+            self.switch_workspace(instance.workspace);
+        }
+        else {
+            self.apply_visibility();
+            self.arrange_workspace();
+        }
+
+        match visible {
+            true => if let Some(window) = self.window_mut(instance.window) {
+                window.request_focus();
+            },
+            false => self.focus_recent_window(),
+        }
+
+        self.update_app_menu(instance.shortcut);
+    }
+
+    /// Focus the most recently used window of the current workspace that is not minimized
+    fn focus_recent_window(&mut self) {
+        let recent = self.window_mru
+            .iter()
+            .filter_map(|win_handle| self.instance_of_window(*win_handle))
+            .find(|instance| instance.workspace == self.current_workspace && !instance.minimized);
+
+        if let Some(instance) = recent
+            && let Some(window) = self.window_mut(instance.window) {
+            window.request_focus();
+        }
+    }
+
+    /// Maximize the window of the instance to the whole desktop, or restore its previous geometry
+    fn toggle_maximize_instance(&mut self, instance: AppInstance) {
+        if instance.minimized || instance.workspace != self.current_workspace {
+            self.set_instance_visible(instance, true);
+        }
+
+        let desktop_rect = self.desktop_rect();
+        if let Some(window) = self.window_mut(instance.window) {
+            window.toggle_maximize(desktop_rect);
+            window.request_focus();
+        }
+    }
+
+    /// Show the windows of the current workspace that are not minimized, hide the other ones
+    fn apply_visibility(&mut self) {
+        for instance in self.app_windows.clone() {
+            let visible = instance.is_shown(self.current_workspace);
+
+            if let Some(window) = self.window_mut(instance.window)
+                && window.is_visible() != visible {
+                window.set_visible(visible);
+            }
+        }
+    }
+
+    /// Indexes in `app_windows` of the tiled windows of the current workspace, in tiling order
+    fn tile_indexes(&self) -> Vec<usize> {
+        self.app_windows
+            .iter()
+            .enumerate()
+            .filter(|(_, instance)| instance.is_tiled(self.current_workspace))
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    /// Arrange the tiled windows of the current workspace with its layout
+    fn arrange_workspace(&mut self) {
+        let windows: Vec<Handle<TuiWindow>> = self.tile_indexes()
+            .into_iter()
+            .map(|index| self.app_windows[index].window)
+            .collect();
+
+        let positions = self.workspaces[self.current_workspace].tiling.arrange(self.desktop_rect(), windows.len());
+
+        for (win_handle, position) in windows.into_iter().zip(positions) {
+            if let Some(window) = self.window_mut(win_handle) {
+                window.move_to(position);
+            }
+        }
+    }
+
+    pub fn switch_workspace(&mut self, workspace: usize) {
+        if workspace >= self.workspaces.len() {
+            return;
+        }
+
+        self.current_workspace = workspace;
+        self.apply_visibility();
+        self.set_layout(self.workspaces[workspace].tiling.layout);
+
+        let workspace_menu_button = self.workspace_menu_button;
+        if let Some(button) = self.appbar().get_mut(workspace_menu_button) {
+            button.set_caption(&format!("Workspace {}", workspace + 1));
+        }
+
+        self.focus_recent_window();
+
+        for index in 0..self.shortcuts.len() {
+            self.update_app_menu(index);
+        }
+    }
+
+    /// Send the focused window to another workspace
+    pub fn move_active_window(&mut self, workspace: usize) {
+        if workspace >= self.workspaces.len() {
+            return;
+        }
+
+        let Some(instance) = self.active_app() else {
+            return;
+        };
+
+        if let Some(app_instance) = self.app_windows.iter_mut().find(|app_instance| app_instance.window == instance.window) {
+            app_instance.workspace = workspace;
+        }
+
+        self.apply_visibility();
+        self.arrange_workspace();
+        self.update_app_menu(instance.shortcut);
+    }
+
+    /// Stop the application of the focused window and close it
+    pub fn kill_active_window(&mut self) {
+        if let Some(instance) = self.active_app() {
+            self.close_instance(instance);
+        }
+    }
+
+    pub fn minimize_active_window(&mut self) {
+        if let Some(instance) = self.active_app() {
+            self.set_instance_visible(instance, false);
+        }
+    }
+
+    pub fn maximize_active_window(&mut self) {
+        if let Some(instance) = self.active_app() {
+            self.toggle_maximize_instance(instance);
+        }
+    }
+
+    /// Open a pane running the command of the shortcut next to the focused pane of the focused window
+    fn split_active_window(&mut self, direction: SplitDirection) {
+        let Some(instance) = self.active_app() else {
+            return;
+        };
+
+        let command = self.shortcuts[instance.shortcut].command.clone();
+        let args = self.shortcuts[instance.shortcut].args.clone();
+        let launch = self.shortcuts[instance.shortcut].launch_options();
+
+        let Some(window) = self.window_mut(instance.window) else {
+            return;
+        };
+
+        if let Err(error) = window.split_active_pane(direction, 0.5, &command, &args, &launch) {
+            self.report_launch_error(instance.shortcut, error);
+        }
+    }
+
+    fn update_active_window(&mut self, update: impl FnOnce(&mut TuiWindow)) {
+        if let Some(instance) = self.active_app()
+            && let Some(window) = self.window_mut(instance.window) {
+            update(window);
+        }
+    }
+
+    /// Move the tabs of the source window into the target window, the source window is closed
+    fn group_windows(&mut self, source: AppInstance, target: AppInstance) {
+        let Some(tabs) = self.window_mut(source.window).map(TuiWindow::take_tabs) else {
+            return;
+        };
+
+        if let Some(window) = self.window_mut(target.window) {
+            window.add_tabs(tabs);
+            window.request_focus();
+        }
+
+        self.app_windows.retain(|instance| instance.window != source.window);
+        self.update_app_menu(source.shortcut);
+        self.arrange_workspace();
+    }
+
+    /// Group the focused window with the previously focused window of the current workspace
+    fn group_active_window(&mut self) {
+        let Some(active) = self.active_app() else {
+            return;
+        };
+
+        let target = self.window_mru
+            .iter()
+            .filter_map(|win_handle| self.instance_of_window(*win_handle))
+            .find(|instance| instance.window != active.window && instance.workspace == self.current_workspace && !instance.minimized);
+
+        if let Some(target) = target {
+            self.group_windows(active, target);
+        }
+    }
+
+    /// Group a window dropped by the user exactly on the title bar of another window
+    fn group_dropped_windows(&mut self) {
+        let windows: Vec<(AppInstance, Point)> = self.app_windows
+            .clone()
+            .into_iter()
+            .filter_map(|instance| self.window_mut(instance.window).map(|window| (instance, window.position())))
+            .collect();
+
+        for (index, (instance, _)) in windows.iter().enumerate() {
+            if !self.window_mut(instance.window).is_some_and(TuiWindow::take_dropped) {
+                continue;
+            }
+
+            if let Some(target) = drop_target(index, &windows, self.current_workspace) {
+                self.group_windows(*instance, windows[target].0);
+                return;
+            }
+        }
+    }
+
+    /// Mark as minimized the windows hidden with their title bar button
+    fn update_minimized_instances(&mut self) {
+        let current_workspace = self.current_workspace;
+        let minimized: Vec<AppInstance> = self.app_windows
+            .clone()
+            .into_iter()
+            .filter(|instance| instance.workspace == current_workspace && !instance.minimized)
+            .filter(|instance| self.window_mut(instance.window).is_some_and(|window| !window.is_visible()))
+            .collect();
+
+        for instance in minimized {
+            self.set_instance_visible(instance, false);
+        }
+    }
+
+    /// Forget the instances whose window was closed by the user or by its application
+    fn remove_closed_instances(&mut self) {
+        let closed: Vec<AppInstance> = self.app_windows
+            .clone()
+            .into_iter()
+            .filter(|instance| self.window_mut(instance.window).is_none())
+            .collect();
+
+        for instance in closed {
+            self.app_windows.retain(|app_instance| app_instance.window != instance.window);
+            self.update_app_menu(instance.shortcut);
+        }
+    }
+
+    /// Move the focused window to the front of the most-recently-used list and forget the closed ones
+    fn update_window_mru(&mut self) {
+        if let Some(instance) = self.active_app()
+            && self.window_mru.first() != Some(&instance.window) {
+            self.window_mru.retain(|handle| *handle != instance.window);
+            self.window_mru.insert(0, instance.window);
+        }
+
+        let open_windows: Vec<Handle<TuiWindow>> = self.app_windows.iter().map(|instance| instance.window).collect();
+        self.window_mru.retain(|handle| open_windows.contains(handle));
+
+        for win_handle in open_windows {
+            if !self.window_mru.contains(&win_handle) {
+                self.window_mru.push(win_handle);
+            }
+        }
+    }
+
+    /// Let the user pick a window in most-recently-used order, then show and focus it
+    pub fn show_window_switcher(&mut self) {
+        self.update_window_mru();
+
+        let mut instances = Vec::new();
+        let mut entries = Vec::new();
+
+        for win_handle in self.window_mru.clone() {
+            let Some(instance) = self.instance_of_window(win_handle) else {
+                continue;
+            };
+            let app_name = format!("{} #{}", self.shortcuts[instance.shortcut].name, instance.number);
+
+            if let Some(window) = self.window_mut(win_handle) {
+                entries.push(SwitcherEntry {
+                    app_name,
+                    title: window.active_parser().and_then(|parser| parser.title()).map(str::to_string),
+                    preview: window.active_parser().map(|parser| parser.preview(PREVIEW_WIDTH, PREVIEW_HEIGHT)).unwrap_or_default(),
+                });
+                instances.push(instance);
+            }
+        }
+
+        if instances.is_empty() {
+            return;
+        }
+
+        let cycle_key = self.key_bindings.key(&DesktopAction::SwitchWindow);
+        let Some(selected) = WindowSwitcher::new(entries, cycle_key).show() else {
+            return;
+        };
+
+        self.set_instance_visible(instances[selected], true);
+        self.update_window_mru();
+    }
+
+    /// Show the menu matching the running instances of the shortcut, building it on first use
+    fn update_app_menu(&mut self, index: usize) {
+        let instances = self.instances(index);
+
+        while self.app_menus[index].len() <= instances.len() {
+            let app_menu = self.build_app_menu(index, self.app_menus[index].len());
+            self.app_menus[index].push(app_menu);
+        }
+
+        let app_menu = self.app_menus[index][instances.len()].clone();
+
+        for (instance, items) in instances.iter().zip(app_menu.instance_items.iter()) {
+            let caption = instance.caption(self.current_workspace);
+
+            if let Some(submenu) = self.menuitem_mut(app_menu.menu, items.submenu) {
+                submenu.set_caption(&caption);
+            }
+        }
+
+        let button_caption = app_button_caption(&self.shortcuts[index].name, &instances);
+
+        if let Some(button) = self.appbar().get_mut(app_menu.button) {
+            button.set_caption(&button_caption);
+        }
+
+        self.app_menu_buttons[index] = app_menu.button;
+    }
+
+    fn build_app_menu(&mut self, index: usize, instance_count: usize) -> AppMenu {
+        let shortcut = self.shortcuts[index].clone();
+        let open_key = self.key_bindings.key(&DesktopAction::OpenApp(shortcut.name.clone()));
+        let mut menu = Menu::new();
+
+        menu.add(Command::new("New instance", open_key, Commands::OpenApp));
+
+        if instance_count > 0 {
+            menu.add(menu::Separator::new());
+        }
+
+        let mut instance_items = Vec::new();
+        for _ in 0..instance_count {
+            let mut instance_menu = Menu::new();
+
+            let focus = instance_menu.add(Command::new("Focus", Key::None, Commands::InstanceFocus));
+            let minimize = instance_menu.add(Command::new("Minimize", Key::None, Commands::InstanceMinimize));
+            let maximize = instance_menu.add(Command::new("Maximize / Restore", Key::None, Commands::InstanceMaximize));
+            let close = instance_menu.add(Command::new("Close", Key::None, Commands::InstanceClose));
+            let submenu = menu.add(SubMenu::new("", instance_menu));
+
+            instance_items.push(InstanceItems {
+                submenu,
+                focus,
+                minimize,
+                maximize,
+                close,
+            });
+        }
+
+        if !shortcut.taskbar.additional_commands.is_empty() {
+            menu.add(menu::Separator::new());
+        }
+
+        for command in &shortcut.taskbar.additional_commands {
+            menu.add(Command::new(&command.name, Key::None, Commands::AppCommand));
+        }
+
+        let menu = self.register_menu(menu);
+        let button = self.appbar().add(MenuButton::with_handle(&shortcut.name, menu, 4 + index as u8, Side::Left));
+
+        AppMenu {
+            menu,
+            button,
+            instance_items,
+        }
+    }
+
+    /// Tiling layout of the current workspace
+    pub fn set_layout(&mut self, layout: Option<TilingLayout>) {
+        self.workspaces[self.current_workspace].tiling.layout = layout;
+        self.arrange_workspace();
+    }
+
+    /// Move the focused tile by `offset` positions in the tiling order, swapping it with the tile there
+    fn swap_active_tile(&mut self, offset: isize) {
+        let tiles = self.tile_indexes();
+        let Some(position) = self.active_tile_position(&tiles) else {
+            return;
+        };
+
+        let target = (position as isize + offset).rem_euclid(tiles.len() as isize) as usize;
+        self.app_windows.swap(tiles[position], tiles[target]);
+        self.arrange_workspace();
+    }
+
+    /// Swap the focused tile with the first master, or the first master with the next tile
+    fn swap_master(&mut self) {
+        let tiles = self.tile_indexes();
+        let Some(position) = self.active_tile_position(&tiles) else {
+            return;
+        };
+
+        let target = match position {
+            0 => 1.min(tiles.len() - 1),
+            _ => 0,
+        };
+
+        self.app_windows.swap(tiles[position], tiles[target]);
+        self.arrange_workspace();
+    }
+
+    /// Shift every tile one position forward or backward, the focus follows its window
+    fn rotate_tiles(&mut self, forward: bool) {
+        let tiles = self.tile_indexes();
+        let mut instances: Vec<AppInstance> = tiles.iter().map(|index| self.app_windows[*index]).collect();
+
+        if instances.is_empty() {
+            return;
+        }
+
+        match forward {
+            true => instances.rotate_right(1),
+            false => instances.rotate_left(1),
+        }
+
+        for (index, instance) in tiles.into_iter().zip(instances) {
+            self.app_windows[index] = instance;
+        }
 
-        Ok(())
+        self.arrange_workspace();
+    }
+
+    fn active_tile_position(&self, tiles: &[usize]) -> Option<usize> {
+        let active = self.active_app()?;
+        tiles.iter().position(|index| self.app_windows[*index].window == active.window)
+    }
+
+    fn update_tiling(&mut self, update: impl FnOnce(&mut Tiling)) {
+        update(&mut self.workspaces[self.current_workspace].tiling);
+        self.arrange_workspace();
+    }
+
+    /// Leave the focused window out of the tiling layouts, or put it back
+    fn toggle_floating(&mut self) {
+        let Some(active) = self.active_app() else {
+            return;
+        };
+
+        if let Some(instance) = self.app_windows.iter_mut().find(|instance| instance.window == active.window) {
+            instance.floating = !instance.floating;
+        }
+
+        self.arrange_workspace();
+    }
+
+    /// Show a problem to the user on the next timer tick
+    pub fn notify(&mut self, title: &str, message: &str) {
+        self.notifications.push((title.to_string(), message.to_string()));
+    }
+
+    /// Tell the user why the command of the shortcut was not started, a cancelled dialog is not shown as an error
+    fn report_launch_error(&mut self, index: usize, error: anyhow::Error) {
+        let name = &self.shortcuts[index].name;
+
+        match error.downcast_ref::<Cancelled>() {
+            Some(cancelled) => dialogs::message("Cancelled", &format!("{cancelled}, \"{name}\" was not started")),
+            None => {
+                let title = format!("Failed to start \"{name}\"");
+                self.notify(&title, &error.to_string());
+            }
+        }
+    }
+
+    /// Windows and tiling state to save
+    fn session(&mut self) -> Session {
+        let mut windows = Vec::new();
+
+        for instance in self.app_windows.clone() {
+            let shortcut = self.shortcuts[instance.shortcut].name.clone();
+
+            let Some(window) = self.window_mut(instance.window) else {
+                continue;
+            };
+
+            let (position, size) = (window.position(), window.size());
+            let tabs = window.tab_states();
+
+            let Some(first_pane) = tabs.first().and_then(|tab| tab.panes.first()).cloned() else {
+                continue;
+            };
+
+            windows.push(WindowState {
+                shortcut,
+                command: first_pane.command,
+                args: first_pane.args,
+                x: position.x,
+                y: position.y,
+                width: size.width,
+                height: size.height,
+                workspace: instance.workspace,
+                minimized: instance.minimized,
+                floating: instance.floating,
+                launch: first_pane.launch,
+                active_tab: window.active_tab,
+                tabs,
+            });
+        }
+
+        Session {
+            current_workspace: self.current_workspace,
+            workspaces: self.workspaces.iter().map(|workspace| workspace.tiling).collect(),
+            windows,
+        }
+    }
+
+    pub fn save_session(&mut self) -> anyhow::Result<()> {
+        self.last_session_save = Instant::now();
+        let session = self.session();
+        save_session(&session)
+    }
+
+    /// Reopen the windows of the last session, the ones that cannot be started are reported
+    fn restore_session(&mut self) {
+        let session = match load_session() {
+            Ok(Some(session)) => session,
+            Ok(None) => return,
+            Err(error) => {
+                self.notify("Failed to restore the session", &error.to_string());
+                return;
+            }
+        };
+
+        for (workspace, tiling) in session.workspaces.into_iter().enumerate().take(self.workspaces.len()) {
+            self.workspaces[workspace].tiling = tiling;
+        }
+
+        let mut problems = Vec::new();
+
+        for window_state in session.windows {
+            let Some(index) = self.shortcuts.iter().position(|shortcut| shortcut.name == window_state.shortcut) else {
+                problems.push(format!("Shortcut \"{}\" no longer exists", window_state.shortcut));
+                continue;
+            };
+
+            if !window_state.launch.shell && find_program(&window_state.command).is_none() {
+                problems.push(format!("Command \"{}\" of \"{}\" was not found", window_state.command, window_state.shortcut));
+                continue;
+            }
+
+            // Sessions saved before the tabs were kept only have the command, the panes are only declared for the main command of the shortcut
+            let panes = match window_state.tabs.is_empty() && window_state.command == self.shortcuts[index].command {
+                true => self.shortcuts[index].panes.clone(),
+                false => Vec::new(),
+            };
+
+            let win_handle = match self.create_window(index, window_state.command, window_state.args, window_state.launch, panes) {
+                Ok(win_handle) => win_handle,
+                Err(error) => {
+                    problems.push(format!("{}: {error}", window_state.shortcut));
+                    continue;
+                }
+            };
+
+            let workspace = window_state.workspace.min(self.workspaces.len() - 1);
+
+            if let Some(instance) = self.app_windows.iter_mut().find(|instance| instance.window == win_handle) {
+                instance.workspace = workspace;
+                instance.minimized = window_state.minimized;
+                instance.floating = window_state.floating;
+            }
+
+            if let Some(window) = self.window_mut(win_handle) {
+                window.move_to(Rect::with_size(window_state.x, window_state.y, window_state.width as u16, window_state.height as u16));
+
+                if !window_state.tabs.is_empty() {
+                    let pane_problems = window.restore_tabs(window_state.tabs, window_state.active_tab);
+                    problems.extend(pane_problems.into_iter().map(|problem| format!("{}: {problem}", window_state.shortcut)));
+                }
+            }
+
+            self.update_app_menu(index);
+        }
+
+        self.switch_workspace(session.current_workspace.min(self.workspaces.len() - 1));
+
+        if !problems.is_empty() {
+            self.notify("Some windows were not restored", &problems.join("\n"));
+        }
+    }
+
+    /// Window running a shortcut, by the id given in the control API
+    fn instance_by_id(&self, id: usize) -> anyhow::Result<AppInstance> {
+        self.app_windows
+            .iter()
+            .find(|instance| instance.id == id)
+            .copied()
+            .ok_or(anyhow::anyhow!("No window with id {id}"))
+    }
+
+    fn shortcut_index(&self, name: &str) -> anyhow::Result<usize> {
+        self.shortcuts
+            .iter()
+            .position(|shortcut| shortcut.name == name)
+            .ok_or(anyhow::anyhow!("Unknown shortcut \"{name}\""))
+    }
+
+    /// Id of the window opened for the shortcut
+    fn opened_window_id(&self, win_handle: Handle<TuiWindow>) -> Response {
+        Response {
+            window: self.instance_of_window(win_handle).map(|instance| instance.id),
+            ..Response::success()
+        }
+    }
+
+    /// Running windows, as listed by the control API
+    fn window_infos(&mut self) -> Vec<WindowInfo> {
+        let mut windows = Vec::new();
+
+        for instance in self.app_windows.clone() {
+            let shortcut = self.shortcuts[instance.shortcut].name.clone();
+
+            let Some(window) = self.window_mut(instance.window) else {
+                continue;
+            };
+
+            windows.push(WindowInfo {
+                id: instance.id,
+                shortcut,
+                number: instance.number,
+                title: window.active_parser().and_then(|parser| parser.title()).map(str::to_string),
+                workspace: instance.workspace + 1,
+                minimized: instance.minimized,
+                floating: instance.floating,
+                pids: window.pids(),
+            });
+        }
+
+        windows
+    }
+
+    /// Answer a request received on the control socket
+    fn handle_control_request(&mut self, request: Request) -> anyhow::Result<Response> {
+        match request {
+            Request::Open { shortcut } => {
+                let index = self.shortcut_index(&shortcut)?;
+                let win_handle = self.open_app(index)?;
+                Ok(self.opened_window_id(win_handle))
+            }
+            Request::Run { shortcut, program, args } => {
+                let index = self.shortcut_index(&shortcut)?;
+                let launch = self.shortcuts[index].launch_options();
+                let win_handle = self.create_window(index, program, args, launch, Vec::new())?;
+                Ok(self.opened_window_id(win_handle))
+            }
+            Request::SendKeys { window, text, keys } => {
+                let instance = self.instance_by_id(window)?;
+                let keys = keys
+                    .iter()
+                    .map(|key| parse_key(key))
+                    .collect::<anyhow::Result<Vec<Key>>>()?;
+
+                let Some(window) = self.window_mut(instance.window) else {
+                    return Err(anyhow::anyhow!("The window was closed"));
+                };
+
+                let mut data = text.into_bytes();
+                for key in keys {
+                    data.extend(to_escape_sequence_vec(key, '\0', window.input_modes()).unwrap_or_default());
+                }
+
+                window.send_input(data);
+                Ok(Response::success())
+            }
+            Request::Focus { window } => {
+                let instance = self.instance_by_id(window)?;
+                self.set_instance_visible(instance, true);
+                Ok(Response::success())
+            }
+            Request::Close { window } => {
+                let instance = self.instance_by_id(window)?;
+                self.close_instance(instance);
+                Ok(Response::success())
+            }
+            Request::Action { action } => {
+                self.run_action(DesktopAction::from_str(&action)?);
+                Ok(Response::success())
+            }
+            Request::List => Ok(Response {
+                windows: Some(self.window_infos()),
+                ..Response::success()
+            }),
+        }
+    }
+
+    /// Detach the client showing the desktop, when it runs in the background
+    pub fn detach(&mut self) {
+        match std::env::var(SERVER_ENV) {
+            Ok(name) => if let Err(error) = detach(&name) {
+                self.notify("Failed to detach", &error.to_string());
+            },
+            Err(_) => self.notify("Failed to detach", "The desktop was not started with \"desktop-tui attach\""),
+        }
+    }
+
+    pub fn exit(&mut self) {
+        // Nothing can be reported anymore
+        self.save_session().ok();
+        std::fs::remove_file(&self.control_socket).ok();
+
+        for instance in self.app_windows.clone() {
+            if let Some(win) = self.window_mut(instance.window) {
+                win.close_command();
+            }
+        }
+
+        self.close()
+    }
+
+    pub fn run_action(&mut self, action: DesktopAction) {
+        match action {
+            DesktopAction::Exit => self.exit(),
+            DesktopAction::Detach => self.detach(),
+            DesktopAction::KillWindow => self.kill_active_window(),
+            DesktopAction::MinimizeWindow => self.minimize_active_window(),
+            DesktopAction::MaximizeWindow => self.maximize_active_window(),
+            DesktopAction::NoArrange => self.set_layout(None),
+            DesktopAction::Cascade => self.set_layout(Some(TilingLayout::Cascade)),
+            DesktopAction::Vertical => self.set_layout(Some(TilingLayout::Vertical)),
+            DesktopAction::Horizontal => self.set_layout(Some(TilingLayout::Horizontal)),
+            DesktopAction::Grid => self.set_layout(Some(TilingLayout::Grid)),
+            DesktopAction::MasterStack => self.set_layout(Some(TilingLayout::MasterStack)),
+            DesktopAction::Dwindle => self.set_layout(Some(TilingLayout::Dwindle)),
+            DesktopAction::Monocle => self.set_layout(Some(TilingLayout::Monocle)),
+            DesktopAction::SwapNext => self.swap_active_tile(1),
+            DesktopAction::SwapPrevious => self.swap_active_tile(-1),
+            DesktopAction::SwapMaster => self.swap_master(),
+            DesktopAction::RotateNext => self.rotate_tiles(true),
+            DesktopAction::RotatePrevious => self.rotate_tiles(false),
+            DesktopAction::GrowMaster => self.update_tiling(|tiling| tiling.resize_master(MASTER_RATIO_STEP)),
+            DesktopAction::ShrinkMaster => self.update_tiling(|tiling| tiling.resize_master(-MASTER_RATIO_STEP)),
+            DesktopAction::AddMaster => self.update_tiling(|tiling| tiling.change_master_count(1)),
+            DesktopAction::RemoveMaster => self.update_tiling(|tiling| tiling.change_master_count(-1)),
+            DesktopAction::ToggleFloating => self.toggle_floating(),
+            DesktopAction::SplitRight => self.split_active_window(SplitDirection::Right),
+            DesktopAction::SplitDown => self.split_active_window(SplitDirection::Down),
+            DesktopAction::ClosePane => self.update_active_window(TuiWindow::close_active_pane),
+            DesktopAction::FocusPaneLeft => self.update_active_window(|window| window.focus_neighbour_pane(PaneDirection::Left)),
+            DesktopAction::FocusPaneRight => self.update_active_window(|window| window.focus_neighbour_pane(PaneDirection::Right)),
+            DesktopAction::FocusPaneUp => self.update_active_window(|window| window.focus_neighbour_pane(PaneDirection::Up)),
+            DesktopAction::FocusPaneDown => self.update_active_window(|window| window.focus_neighbour_pane(PaneDirection::Down)),
+            DesktopAction::GrowPane => self.update_active_window(|window| window.resize_active_pane(PANE_RATIO_STEP)),
+            DesktopAction::ShrinkPane => self.update_active_window(|window| window.resize_active_pane(-PANE_RATIO_STEP)),
+            DesktopAction::ZoomPane => self.update_active_window(TuiWindow::toggle_zoom),
+            DesktopAction::NextTab => self.update_active_window(|window| window.cycle_tab(1)),
+            DesktopAction::PreviousTab => self.update_active_window(|window| window.cycle_tab(-1)),
+            DesktopAction::GroupWindow => self.group_active_window(),
+            DesktopAction::SwitchWindow => self.show_window_switcher(),
+            DesktopAction::Workspace(number) => self.switch_workspace(number - 1),
+            DesktopAction::NextWorkspace => self.switch_workspace((self.current_workspace + 1) % self.workspaces.len()),
+            DesktopAction::PreviousWorkspace => self.switch_workspace((self.current_workspace + self.workspaces.len() - 1) % self.workspaces.len()),
+            DesktopAction::MoveToWorkspace(number) => self.move_active_window(number - 1),
+            DesktopAction::OpenApp(name) => {
+                if let Some(index) = self.shortcuts.iter().position(|shortcut| shortcut.name == name)
+                    && let Err(error) = self.open_app(index) {
+                    self.report_launch_error(index, error);
+                }
+            },
+            // Handled by the window itself
+            DesktopAction::SendPrefix => {}
+        }
     }
 }
 
@@ -68,65 +1146,240 @@ impl OnPaint for MyDesktop {
     }
 }
 
+impl OnResize for MyDesktop {
+    fn on_resize(&mut self, _old_size: Size, _new_size: Size) {
+        self.arrange_workspace();
+    }
+}
+
 impl DesktopEvents for MyDesktop {
     fn on_start(&mut self) {
-        let shortcuts = self.shortcuts.clone();
+        self.build_appbar();
+        self.time_label = self.appbar().add(appbar::Label::new(&time_to_string(), 0, Side::Right));
+
+        let timer = self.timer().expect("Failed to get timer");
+        timer.start(Duration::from_millis(50));
+
+        if self.restore {
+            self.restore_session();
+        }
+
+        let control_socket = self.control_socket.clone();
+        if let Err(error) = serve(&control_socket, self.control_tx.clone()) {
+            self.notify("The control socket is not available", &error.to_string());
+        }
+
+        let config_path = self.config_path.clone().or_else(default_config_path);
+        match watch_files(&self.shortcut_dirs, config_path, self.file_changes_tx.clone()) {
+            Ok(file_watcher) => self.file_watcher = Some(file_watcher),
+            Err(error) => self.notify("Shortcut and config changes are not watched", &error.to_string()),
+        }
+    }
+
+    fn on_update_window_count(&mut self, _count: usize) {
+        self.remove_closed_instances();
+        self.arrange_workspace();
+    }
+}
+
+impl MyDesktop {
+    /// Menus of the appbar, built again when the shortcuts or the key bindings change.
+    /// Registered menus cannot be removed, the previous ones are no longer shown
+    fn build_appbar(&mut self) {
+        let key_bindings = self.key_bindings.clone();
         let mut desktop_menu = Menu::new();
 
-        desktop_menu.add(Command::new("Exit", Key::None, Commands::Exit));
+        desktop_menu.add(Command::new("Switch window", key_bindings.key(&DesktopAction::SwitchWindow), Commands::SwitchWindow));
+        desktop_menu.add(Command::new("Minimize window", key_bindings.key(&DesktopAction::MinimizeWindow), Commands::MinimizeWindow));
+        desktop_menu.add(Command::new("Maximize / Restore window", key_bindings.key(&DesktopAction::MaximizeWindow), Commands::MaximizeWindow));
+        desktop_menu.add(Command::new("Kill window", key_bindings.key(&DesktopAction::KillWindow), Commands::KillWindow));
+
+        let pane_actions = [
+            ("Split right", DesktopAction::SplitRight),
+            ("Split down", DesktopAction::SplitDown),
+            ("Close pane", DesktopAction::ClosePane),
+            ("Focus left", DesktopAction::FocusPaneLeft),
+            ("Focus right", DesktopAction::FocusPaneRight),
+            ("Focus up", DesktopAction::FocusPaneUp),
+            ("Focus down", DesktopAction::FocusPaneDown),
+            ("Grow pane", DesktopAction::GrowPane),
+            ("Shrink pane", DesktopAction::ShrinkPane),
+            ("Zoom / Unzoom pane", DesktopAction::ZoomPane),
+        ];
+
+        let tab_actions = [
+            ("Next tab", DesktopAction::NextTab),
+            ("Previous tab", DesktopAction::PreviousTab),
+            ("Group with previous window", DesktopAction::GroupWindow),
+        ];
+
+        let mut pane_menu = Menu::new();
+        let mut pane_items: Vec<(Handle<Command>, DesktopAction)> = pane_actions
+            .into_iter()
+            .map(|(caption, action)| (pane_menu.add(Command::new(caption, key_bindings.key(&action), Commands::PaneCommand)), action))
+            .collect();
+
+        let mut tab_menu = Menu::new();
+        pane_items.extend(tab_actions
+            .into_iter()
+            .map(|(caption, action)| (tab_menu.add(Command::new(caption, key_bindings.key(&action), Commands::PaneCommand)), action)));
+
+        desktop_menu.add(SubMenu::new("Panes", pane_menu));
+        desktop_menu.add(SubMenu::new("Tabs", tab_menu));
+        desktop_menu.add(menu::Separator::new());
+        desktop_menu.add(Command::new("Detach", key_bindings.key(&DesktopAction::Detach), Commands::Detach));
+        desktop_menu.add(Command::new("Exit", key_bindings.key(&DesktopAction::Exit), Commands::Exit));
 
         let desktop_menu_button = self.appbar().add(MenuButton::new("Desktop", desktop_menu, 0, Side::Left));
 
         let mut tilling_menu = Menu::new();
 
-        tilling_menu.add(SingleChoice::new("No arrangement", Key::None, Commands::NoArrange, true));
-        tilling_menu.add(SingleChoice::new("Cascade", Key::None, Commands::Cascade, false));
-        tilling_menu.add(SingleChoice::new("Vertical", Key::None, Commands::Vertical, false));
-        tilling_menu.add(SingleChoice::new("Horizontal", Key::None, Commands::Horizontal, false));
-        tilling_menu.add(SingleChoice::new("Grid", Key::None, Commands::Grid, false));
+        let arrange_choices = vec![
+            tilling_menu.add(SingleChoice::new("No arrangement", key_bindings.key(&DesktopAction::NoArrange), Commands::NoArrange, true)),
+            tilling_menu.add(SingleChoice::new("Cascade", key_bindings.key(&DesktopAction::Cascade), Commands::Cascade, false)),
+            tilling_menu.add(SingleChoice::new("Vertical", key_bindings.key(&DesktopAction::Vertical), Commands::Vertical, false)),
+            tilling_menu.add(SingleChoice::new("Horizontal", key_bindings.key(&DesktopAction::Horizontal), Commands::Horizontal, false)),
+            tilling_menu.add(SingleChoice::new("Grid", key_bindings.key(&DesktopAction::Grid), Commands::Grid, false)),
+            tilling_menu.add(SingleChoice::new("Master / stack", key_bindings.key(&DesktopAction::MasterStack), Commands::MasterStack, false)),
+            tilling_menu.add(SingleChoice::new("Dwindle", key_bindings.key(&DesktopAction::Dwindle), Commands::Dwindle, false)),
+            tilling_menu.add(SingleChoice::new("Monocle", key_bindings.key(&DesktopAction::Monocle), Commands::Monocle, false)),
+        ];
 
-        let arrange_menu_button = self.appbar().add(MenuButton::new("Tilling", tilling_menu, 1, Side::Left));
+        tilling_menu.add(menu::Separator::new());
 
-        let separator = self.appbar().add(appbar::Separator::new(2, Side::Left));
+        let tile_actions = [
+            ("Swap with next", DesktopAction::SwapNext),
+            ("Swap with previous", DesktopAction::SwapPrevious),
+            ("Swap with master", DesktopAction::SwapMaster),
+            ("Rotate forward", DesktopAction::RotateNext),
+            ("Rotate backward", DesktopAction::RotatePrevious),
+            ("Grow master", DesktopAction::GrowMaster),
+            ("Shrink master", DesktopAction::ShrinkMaster),
+            ("Add master", DesktopAction::AddMaster),
+            ("Remove master", DesktopAction::RemoveMaster),
+            ("Toggle floating", DesktopAction::ToggleFloating),
+        ];
 
-        let mut app_menues = vec![Handle::<Menu>::None; shortcuts.len()];
-        let mut app_menu_buttons = vec![Handle::<MenuButton>::None; shortcuts.len()];
-        for (index, shortcut) in shortcuts.iter().enumerate() {
-            let mut menu = Menu::new();
+        let tile_items = tile_actions
+            .into_iter()
+            .map(|(caption, action)| (tilling_menu.add(Command::new(caption, key_bindings.key(&action), Commands::TileCommand)), action))
+            .collect();
 
-            menu.add(Command::new("Hide", Key::None, Commands::AppVisibilityToggle));
-            menu.add(Command::new("Start", Key::None, Commands::OpenApp));
-            menu.add(Command::new("Close", Key::None, Commands::CloseApp));
+        let tilling_menu = self.register_menu(tilling_menu);
+        let arrange_menu_button = self.appbar().add(MenuButton::with_handle("Tilling", tilling_menu, 1, Side::Left));
 
-            if !shortcut.taskbar.additional_commands.is_empty() {
-                menu.add(menu::Separator::new());
-            }
+        let mut workspace_menu = Menu::new();
+        let mut move_menu = Menu::new();
+        let mut workspace_choices = Vec::new();
+        let mut move_to_workspace_items = Vec::new();
 
-            for command in &shortcut.taskbar.additional_commands {
-                menu.add(Command::new(&command.name, Key::None, Commands::AppCommand));
-            }
+        for workspace in 0..self.workspaces.len() {
+            let number = workspace + 1;
+            let caption = format!("Workspace {number}");
 
-            app_menues[index] = self.register_menu(menu);
-            app_menu_buttons[index] = self.appbar().add(MenuButton::with_handle(&shortcut.name, app_menues[index], 2 + index as u8, Side::Left));
+            workspace_choices.push(workspace_menu.add(SingleChoice::new(&caption, key_bindings.key(&DesktopAction::Workspace(number)), Commands::Workspace, workspace == 0)));
+            move_to_workspace_items.push(move_menu.add(Command::new(&caption, key_bindings.key(&DesktopAction::MoveToWorkspace(number)), Commands::MoveToWorkspace)));
         }
 
-        self.time_label = self.appbar().add(appbar::Label::new(&time_to_string(), 0, Side::Right));
+        workspace_menu.add(menu::Separator::new());
+        workspace_menu.add(SubMenu::new("Move window to", move_menu));
+
+        let workspace_menu = self.register_menu(workspace_menu);
+        let workspace_menu_button = self.appbar().add(MenuButton::with_handle("Workspace 1", workspace_menu, 2, Side::Left));
+
+        let separator = self.appbar().add(appbar::Separator::new(3, Side::Left));
 
         self.desktop_menu = desktop_menu_button;
         self.arrange_menu = arrange_menu_button;
+        self.tilling_menu = tilling_menu;
+        self.arrange_choices = arrange_choices;
+        self.tile_items = tile_items;
+        self.pane_items = pane_items;
+        self.workspace_menu = workspace_menu;
+        self.workspace_menu_button = workspace_menu_button;
+        self.workspace_choices = workspace_choices;
+        self.move_to_workspace_items = move_to_workspace_items;
         self.separator = separator;
-        self.app_menues = app_menues;
-        self.app_menu_buttons = app_menu_buttons;
 
-        let timer = self.timer().expect("Failed to get timer");
-        timer.start(Duration::from_millis(2000));
+        self.app_menus = vec![Vec::new(); self.shortcuts.len()];
+        self.app_menu_buttons = vec![Handle::None; self.shortcuts.len()];
+        self.broken_shortcut_buttons.clear();
+
+        for index in 0..self.shortcuts.len() {
+            let shortcut = &self.shortcuts[index];
+
+            // The windows of a shortcut that broke while they run stay reachable
+            if shortcut.errors.is_empty() || self.app_windows.iter().any(|instance| instance.shortcut == index) {
+                self.update_app_menu(index);
+                continue;
+            }
+
+            let mut button = appbar::Button::with_tooltip(&shortcut.name, &shortcut.errors.join("\n"), 4 + index as u8, Side::Left);
+            button.set_enabled(false);
+
+            let button = self.appbar().add(button);
+            self.broken_shortcut_buttons.push(button);
+        }
     }
 
-    fn on_update_window_count(&mut self, _count: usize) {
-        let m = self.arrange_method;
+    /// Errors of the broken shortcuts
+    pub fn notify_shortcut_errors(&mut self) {
+        let errors: Vec<String> = self.shortcuts
+            .iter()
+            .flat_map(|shortcut| shortcut.errors.clone())
+            .collect();
 
-        if let Some(method) = m {
-            self.arrange_windows(method);
+        if !errors.is_empty() {
+            self.notify("Some shortcuts are disabled", &errors.join("\n"));
+        }
+    }
+
+    /// Parse the shortcuts and the config again and rebuild the appbar.
+    /// Windows stay attached to the shortcut with the same name, a removed shortcut is kept while it has windows
+    fn reload(&mut self) {
+        let mut problems = Vec::new();
+
+        match parse_config_file(self.config_path.clone()) {
+            Ok(config) => {
+                self.key_bindings = config.keybindings;
+                self.session_autosave = config.session_autosave.map(Duration::from_secs);
+                self.shortcut_defaults = config.defaults;
+
+                for instance in self.app_windows.clone() {
+                    let key_bindings = self.key_bindings.clone();
+                    if let Some(window) = self.window_mut(instance.window) {
+                        window.set_key_bindings(key_bindings);
+                    }
+                }
+            }
+            Err(error) => problems.push(format!("Config: {error}")),
+        }
+
+        match parse_shortcut_dirs(&self.shortcut_dirs, &self.shortcut_defaults) {
+            Ok((mut shortcuts, _)) => {
+                for (index, shortcut) in self.shortcuts.iter().enumerate() {
+                    if !shortcuts.iter().any(|new_shortcut| new_shortcut.name == shortcut.name)
+                        && self.app_windows.iter().any(|instance| instance.shortcut == index) {
+                        shortcuts.push(shortcut.clone());
+                    }
+                }
+
+                for instance in self.app_windows.iter_mut() {
+                    let name = &self.shortcuts[instance.shortcut].name;
+                    instance.shortcut = shortcuts.iter().position(|shortcut| shortcut.name == *name).unwrap();
+                }
+
+                self.shortcuts = shortcuts;
+                self.notify_shortcut_errors();
+            }
+            Err(error) => problems.push(format!("Shortcuts: {error}")),
+        }
+
+        self.build_appbar();
+        self.switch_workspace(self.current_workspace);
+
+        if !problems.is_empty() {
+            self.notify("Failed to reload", &problems.join("\n"));
         }
     }
 }
@@ -135,134 +1388,282 @@ impl AppBarEvents for MyDesktop {
     fn on_update(&self, app_bar: &mut AppBar) {
         app_bar.show(self.desktop_menu);
         app_bar.show(self.arrange_menu);
+        app_bar.show(self.workspace_menu_button);
         app_bar.show(self.separator);
 
         for app_menu in self.app_menu_buttons.iter() {
             app_bar.show(*app_menu);
         }
 
+        for button in self.broken_shortcut_buttons.iter() {
+            app_bar.show(*button);
+        }
+
         app_bar.show(self.time_label);
     }
 }
 
 impl MenuEvents for MyDesktop {
+    /// Items of a menu only know it once it is shown, the choices are selected here
+    fn on_menu_open(&self, menu: &mut Menu) {
+        let choice_index = match self.workspaces[self.current_workspace].tiling.layout {
+            None => 0,
+            Some(TilingLayout::Cascade) => 1,
+            Some(TilingLayout::Vertical) => 2,
+            Some(TilingLayout::Horizontal) => 3,
+            Some(TilingLayout::Grid) => 4,
+            Some(TilingLayout::MasterStack) => 5,
+            Some(TilingLayout::Dwindle) => 6,
+            Some(TilingLayout::Monocle) => 7,
+        };
+
+        if let Some(choice) = menu.get_mut(self.arrange_choices[choice_index]) {
+            choice.set_selected();
+        }
+
+        if let Some(choice) = menu.get_mut(self.workspace_choices[self.current_workspace]) {
+            choice.set_selected();
+        }
+    }
+
     fn on_command(&mut self, menu: Handle<Menu>, item: Handle<Command>, command: Commands) {
         match command {
-            Commands::Exit => {
-                for window in self.app_windows.clone().values() {
-                    if let Some(win) = self.window_mut(*window) {
-                        win.close_command();
-                    }
-                }
-
-                self.close()
-            },
-            Commands::OpenApp | Commands::CloseApp | Commands::AppVisibilityToggle | Commands::AppCommand => {
-                let mut app = None;
+            Commands::Exit => self.exit(),
+            Commands::Detach => self.detach(),
+            Commands::KillWindow => self.kill_active_window(),
+            Commands::MinimizeWindow => self.minimize_active_window(),
+            Commands::MaximizeWindow => self.maximize_active_window(),
+            Commands::SwitchWindow => self.show_window_switcher(),
+            Commands::TileCommand | Commands::PaneCommand => {
+                let action = self.tile_items
+                    .iter()
+                    .chain(self.pane_items.iter())
+                    .find(|(tile_item, _)| *tile_item == item)
+                    .map(|(_, action)| action.clone());
 
-                for (index, app_menu) in self.app_menues.iter().enumerate() {
-                    if &menu == app_menu {
-                        app = Some(index);
-                    }
+                if let Some(action) = action {
+                    self.run_action(action);
                 }
+            }
+            Commands::MoveToWorkspace => {
+                if let Some(workspace) = self.move_to_workspace_items.iter().position(|move_item| *move_item == item) {
+                    self.move_active_window(workspace);
+                }
+            }
+            Commands::OpenApp | Commands::AppCommand => {
+                let app = self.app_menus
+                    .iter()
+                    .position(|app_menus| app_menus.iter().any(|app_menu| app_menu.menu == menu));
 
-                if let Some(index) = app {
-                    let win_handle = match self.app_windows.get(&index) {
-                        Some(win_handle) => Some(*win_handle),
-                        None => {
-                            match command {
-                                Commands::OpenApp => {
-                                    let command = self.shortcuts[index].command.clone();
-                                    let args = self.shortcuts[index].args.clone();
-                                    self.create_window(index, command, args).ok();
-                                },
-                                Commands::AppCommand => {
-                                    let shortcut = self.shortcuts[index].clone();
-                                    let item = self.menuitem_mut(menu, item).unwrap();
-
-                                    for command in shortcut.taskbar.additional_commands {
-                                        if item.caption() == command.name {
-                                            self.create_window(index, command.command, command.args).ok();
-                                            break;
-                                        }
-                                    }
-                                },
-                                _ => {}
-                            }
+                let Some(index) = app else {
+                    return;
+                };
 
-                            None
+                match command {
+                    Commands::OpenApp => {
+                        if let Err(error) = self.open_app(index) {
+                            self.report_launch_error(index, error);
                         }
-                    };
-
-                    let mut visibility_item = None;
-
-                    if let Some(win_handle) = win_handle {
-                        if let Some(window) = self.window_mut(win_handle) {
-                            match command {
-                                Commands::AppVisibilityToggle => match window.is_visible() {
-                                    true => {
-                                        window.set_visible(false);
-                                        visibility_item = Some("Show");
-                                    },
-                                    false => {
-                                        window.set_visible(true);
-                                        visibility_item = Some("Hide");
-                                    }
-                                }
-                                Commands::OpenApp => {}
-                                Commands::CloseApp => {
-                                    window.close_command();
-                                    self.app_windows.remove(&index);
+                    }
+                    Commands::AppCommand => {
+                        let shortcut = self.shortcuts[index].clone();
+                        let Some(item) = self.menuitem_mut(menu, item) else {
+                            return;
+                        };
+
+                        for command in shortcut.taskbar.additional_commands.clone() {
+                            if item.caption() == command.name {
+                                let launch = command.launch_options(&shortcut);
+                                if let Err(error) = self.create_window(index, command.command, command.args, launch, Vec::new()) {
+                                    self.report_launch_error(index, error);
                                 }
-                                _ => {}
-                            }
-                        }
-                        else {
-                            match command {
-                                Commands::OpenApp => {
-                                    let command = self.shortcuts[index].command.clone();
-                                    let args = self.shortcuts[index].args.clone();
-                                    self.create_window(index, command, args).ok();
-                                },
-                                _ => {}
+                                break;
                             }
                         }
-                    }
+                    },
+                    _ => {}
+                }
+            }
+            Commands::InstanceFocus | Commands::InstanceMinimize | Commands::InstanceMaximize | Commands::InstanceClose => {
+                let Some(instance) = self.instance_of_item(item) else {
+                    return;
+                };
 
-                    if let Some(name) = visibility_item {
-                        let item = self.menuitem_mut(menu, item).unwrap();
-                        item.set_caption(name);
-                    }
+                match command {
+                    Commands::InstanceFocus => self.set_instance_visible(instance, true),
+                    Commands::InstanceMinimize => self.set_instance_visible(instance, false),
+                    Commands::InstanceMaximize => self.toggle_maximize_instance(instance),
+                    Commands::InstanceClose => self.close_instance(instance),
+                    _ => {}
                 }
             }
             _ => {}
         }
     }
 
-    fn on_select(&mut self, _menu: Handle<Menu>, _item: Handle<SingleChoice>, command: Commands) {
+    fn on_select(&mut self, _menu: Handle<Menu>, item: Handle<SingleChoice>, command: Commands) {
         match command {
-            Commands::NoArrange => self.arrange_method = None,
-            Commands::Cascade => self.arrange_method = Some(desktop::ArrangeWindowsMethod::Cascade),
-            Commands::Vertical => self.arrange_method = Some(desktop::ArrangeWindowsMethod::Vertical),
-            Commands::Horizontal => self.arrange_method = Some(desktop::ArrangeWindowsMethod::Horizontal),
-            Commands::Grid => self.arrange_method = Some(desktop::ArrangeWindowsMethod::Grid),
+            Commands::Workspace => {
+                if let Some(workspace) = self.workspace_choices.iter().position(|choice| *choice == item) {
+                    self.switch_workspace(workspace);
+                }
+            }
+            Commands::NoArrange => self.set_layout(None),
+            Commands::Cascade => self.set_layout(Some(TilingLayout::Cascade)),
+            Commands::Vertical => self.set_layout(Some(TilingLayout::Vertical)),
+            Commands::Horizontal => self.set_layout(Some(TilingLayout::Horizontal)),
+            Commands::Grid => self.set_layout(Some(TilingLayout::Grid)),
+            Commands::MasterStack => self.set_layout(Some(TilingLayout::MasterStack)),
+            Commands::Dwindle => self.set_layout(Some(TilingLayout::Dwindle)),
+            Commands::Monocle => self.set_layout(Some(TilingLayout::Monocle)),
             _ => {}
         }
-        let m = self.arrange_method;
-
-        if let Some(method) = m {
-            self.arrange_windows(method);
-        }
     }
 }
 
 impl TimerEvents for MyDesktop {
     fn on_update(&mut self, _: u64) -> EventProcessStatus {
+        let mut status = EventProcessStatus::Ignored;
+
+        while let Ok(action) = self.action_rx.try_recv() {
+            self.run_action(action);
+            status = EventProcessStatus::Processed;
+        }
+
+        while let Ok(ControlRequest { request, reply }) = self.control_rx.try_recv() {
+            let response = self.handle_control_request(request).unwrap_or_else(Response::error);
+            reply.send_blocking(response).ok();
+            status = EventProcessStatus::Processed;
+        }
+
+        while self.file_changes_rx.try_recv().is_ok() {
+            self.changed_at = Some(Instant::now());
+        }
+
+        if let Some(changed_at) = self.changed_at
+            && changed_at.elapsed() >= RELOAD_DEBOUNCE {
+            self.changed_at = None;
+            self.reload();
+            status = EventProcessStatus::Processed;
+        }
+
+        self.remove_closed_instances();
+        self.update_minimized_instances();
+        self.group_dropped_windows();
+        self.update_window_mru();
+
+        if let Some(session_autosave) = self.session_autosave
+            && self.last_session_save.elapsed() >= session_autosave
+            && let Err(error) = self.save_session() {
+            self.notify("Failed to save the session", &error.to_string());
+        }
+
+        if !self.notifications.is_empty() {
+            for (title, message) in std::mem::take(&mut self.notifications) {
+                dialogs::error(&title, &message);
+            }
+
+            status = EventProcessStatus::Processed;
+        }
+
+        let time = time_to_string();
         let time_label_handle = self.time_label;
         let time_label = self.appbar().get_mut(time_label_handle).unwrap();
 
-        time_label.set_caption(&time_to_string());
+        if time_label.caption() != time {
+            time_label.set_caption(&time);
+            status = EventProcessStatus::Processed;
+        }
+
+        status
+    }
+}
 
-        EventProcessStatus::Processed
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn instance(shortcut: usize, number: usize, workspace: usize, minimized: bool) -> AppInstance {
+        AppInstance {
+            id: number,
+            shortcut,
+            number,
+            window: Handle::None,
+            workspace,
+            minimized,
+            floating: false,
+        }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn numbers_instances() {
+        assert_eq!(free_instance_number(&[], 0), 1);
+        assert_eq!(free_instance_number(&[instance(0, 1, 0, false), instance(0, 2, 0, false)], 0), 3);
+        assert_eq!(free_instance_number(&[instance(0, 1, 0, false), instance(0, 3, 0, false)], 0), 2);
+        assert_eq!(free_instance_number(&[instance(1, 1, 0, false)], 0), 1);
+    }
+
+    #[test]
+    fn captions_instances() {
+        assert_eq!(instance(0, 2, 0, false).caption(0), "#2");
+        assert_eq!(instance(0, 2, 1, false).caption(0), "#2 - Workspace 2");
+        assert_eq!(instance(0, 2, 1, true).caption(1), "#2 (minimized)");
+        assert_eq!(instance(0, 2, 0, true).caption(1), "#2 - Workspace 1 (minimized)");
+
+        assert_eq!(app_button_caption("Shell", &[instance(0, 1, 0, false)]), "Shell");
+        assert_eq!(app_button_caption("Shell", &[instance(0, 1, 0, true), instance(0, 2, 0, false), instance(0, 3, 0, true)]), "Shell (2 minimized)");
+    }
+
+    #[test]
+    fn shows_current_workspace() {
+        let mut floating = instance(0, 3, 1, false);
+        floating.floating = true;
+
+        assert!(instance(0, 1, 1, false).is_shown(1));
+        assert!(!instance(0, 1, 0, false).is_shown(1));
+        assert!(!instance(0, 1, 1, true).is_shown(1));
+        assert!(instance(0, 1, 1, false).is_tiled(1));
+        assert!(floating.is_shown(1));
+        assert!(!floating.is_tiled(1));
+    }
+
+    #[test]
+    fn starts_in_shortcut_workspace() {
+        assert_eq!(start_workspace(None, 4, 2), 2);
+        assert_eq!(start_workspace(Some(1), 4, 2), 0);
+        assert_eq!(start_workspace(Some(4), 4, 2), 3);
+        assert_eq!(start_workspace(Some(5), 4, 2), 2);
+        assert_eq!(start_workspace(Some(0), 4, 2), 0);
+    }
+
+    #[test]
+    fn finds_drop_targets() {
+        let windows = [
+            (instance(0, 1, 0, false), Point::new(10, 5)),
+            (instance(0, 2, 0, false), Point::new(10, 5)),
+            (instance(1, 1, 0, false), Point::new(30, 8)),
+            (instance(1, 2, 0, true), Point::new(40, 2)),
+            (instance(2, 1, 1, false), Point::new(50, 3)),
+            (instance(2, 2, 0, false), Point::new(41, 2)),
+            (instance(3, 1, 0, false), Point::new(50, 3)),
+            (instance(3, 2, 0, false), Point::new(31, 8)),
+        ];
+
+        // (dropped window, target)
+        let cases = [
+            (0, Some(1)),
+            (1, Some(0)),
+            // Overlapping the title bar is not enough
+            (2, None),
+            (7, None),
+            // Minimized and other workspace windows are not targets
+            (5, None),
+            (6, None),
+            (8, None),
+        ];
+
+        for (dropped, expected) in cases {
+            assert_eq!(drop_target(dropped, &windows, 0), expected, "window {dropped}");
+        }
+    }
+}
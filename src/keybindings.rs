@@ -0,0 +1,354 @@
+use anyhow::anyhow;
+use appcui::input::{Key, KeyCode, KeyModifier};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::str::FromStr;
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum DesktopAction {
+    Exit,
+    /// Detach the client when the desktop runs in the background, see `desktop-tui attach`
+    Detach,
+    KillWindow,
+    MinimizeWindow,
+    /// Maximize the focused window, or restore its previous geometry
+    MaximizeWindow,
+    NoArrange,
+    Cascade,
+    Vertical,
+    Horizontal,
+    Grid,
+    MasterStack,
+    Dwindle,
+    Monocle,
+    /// Swap the focused tile with the next or previous one, or with the first master
+    SwapNext,
+    SwapPrevious,
+    SwapMaster,
+    /// Shift every tile one position
+    RotateNext,
+    RotatePrevious,
+    /// Change the width of the master area, or its number of windows
+    GrowMaster,
+    ShrinkMaster,
+    AddMaster,
+    RemoveMaster,
+    /// Leave the focused window out of the tiling layouts, or put it back
+    ToggleFloating,
+    /// Open a pane running the shortcut command next to the focused pane
+    SplitRight,
+    SplitDown,
+    ClosePane,
+    FocusPaneLeft,
+    FocusPaneRight,
+    FocusPaneUp,
+    FocusPaneDown,
+    GrowPane,
+    ShrinkPane,
+    /// Let the focused pane cover its window, or show every pane again
+    ZoomPane,
+    /// Show the next or previous tab of the focused window
+    NextTab,
+    PreviousTab,
+    /// Move the tabs of the focused window into the previously focused window
+    GroupWindow,
+    /// Show the window switcher, cycling through the windows while it is open
+    SwitchWindow,
+    /// Start the shortcut with the given name
+    OpenApp(String),
+    /// Switch to the workspace with the given number, starting at 1
+    Workspace(usize),
+    NextWorkspace,
+    PreviousWorkspace,
+    /// Send the focused window to the workspace with the given number, starting at 1
+    MoveToWorkspace(usize),
+    /// Send the prefix key itself to the focused application
+    SendPrefix,
+}
+
+impl FromStr for DesktopAction {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(shortcut_name) = s.strip_prefix("open:") {
+            return match shortcut_name.trim() {
+                "" => Err(anyhow!("Missing shortcut name in \"{s}\"")),
+                shortcut_name => Ok(DesktopAction::OpenApp(shortcut_name.to_string())),
+            };
+        }
+
+        if let Some(number) = s.strip_prefix("workspace:") {
+            return Ok(DesktopAction::Workspace(parse_workspace_number(number)?));
+        }
+
+        if let Some(number) = s.strip_prefix("move_to_workspace:") {
+            return Ok(DesktopAction::MoveToWorkspace(parse_workspace_number(number)?));
+        }
+
+        match s {
+            "exit" => Ok(DesktopAction::Exit),
+            "detach" => Ok(DesktopAction::Detach),
+            "kill_window" => Ok(DesktopAction::KillWindow),
+            // "hide_window" is the name used before windows could be minimized
+            "minimize_window" | "hide_window" => Ok(DesktopAction::MinimizeWindow),
+            "maximize_window" => Ok(DesktopAction::MaximizeWindow),
+            "no_arrange" => Ok(DesktopAction::NoArrange),
+            "cascade" => Ok(DesktopAction::Cascade),
+            "vertical" => Ok(DesktopAction::Vertical),
+            "horizontal" => Ok(DesktopAction::Horizontal),
+            "grid" => Ok(DesktopAction::Grid),
+            "master_stack" => Ok(DesktopAction::MasterStack),
+            "dwindle" => Ok(DesktopAction::Dwindle),
+            "monocle" => Ok(DesktopAction::Monocle),
+            "swap_next" => Ok(DesktopAction::SwapNext),
+            "swap_previous" => Ok(DesktopAction::SwapPrevious),
+            "swap_master" => Ok(DesktopAction::SwapMaster),
+            "rotate_next" => Ok(DesktopAction::RotateNext),
+            "rotate_previous" => Ok(DesktopAction::RotatePrevious),
+            "grow_master" => Ok(DesktopAction::GrowMaster),
+            "shrink_master" => Ok(DesktopAction::ShrinkMaster),
+            "add_master" => Ok(DesktopAction::AddMaster),
+            "remove_master" => Ok(DesktopAction::RemoveMaster),
+            "toggle_floating" => Ok(DesktopAction::ToggleFloating),
+            "split_right" => Ok(DesktopAction::SplitRight),
+            "split_down" => Ok(DesktopAction::SplitDown),
+            "close_pane" => Ok(DesktopAction::ClosePane),
+            "focus_pane_left" => Ok(DesktopAction::FocusPaneLeft),
+            "focus_pane_right" => Ok(DesktopAction::FocusPaneRight),
+            "focus_pane_up" => Ok(DesktopAction::FocusPaneUp),
+            "focus_pane_down" => Ok(DesktopAction::FocusPaneDown),
+            "grow_pane" => Ok(DesktopAction::GrowPane),
+            "shrink_pane" => Ok(DesktopAction::ShrinkPane),
+            "zoom_pane" => Ok(DesktopAction::ZoomPane),
+            "next_tab" => Ok(DesktopAction::NextTab),
+            "previous_tab" => Ok(DesktopAction::PreviousTab),
+            "group_window" => Ok(DesktopAction::GroupWindow),
+            "switch_window" => Ok(DesktopAction::SwitchWindow),
+            "next_workspace" => Ok(DesktopAction::NextWorkspace),
+            "previous_workspace" => Ok(DesktopAction::PreviousWorkspace),
+            "send_prefix" => Ok(DesktopAction::SendPrefix),
+            _ => Err(anyhow!("Unknown desktop action \"{s}\"")),
+        }
+    }
+}
+
+fn parse_workspace_number(number: &str) -> anyhow::Result<usize> {
+    match number.parse::<usize>() {
+        Ok(number) if number >= 1 => Ok(number),
+        _ => Err(anyhow!("Invalid workspace number \"{number}\", workspaces start at 1")),
+    }
+}
+
+/// Key chords and prefix sequences bound to desktop actions, keys bound here are never forwarded to the applications
+#[derive(Clone, Debug, Deserialize)]
+#[serde(try_from = "HashMap<String, String>")]
+pub struct KeyBindings {
+    /// One chord, or a prefix chord followed by a key
+    bindings: HashMap<DesktopAction, Vec<Key>>,
+}
+
+impl KeyBindings {
+    /// Key bound to the action, `Key::None` if there is none or if it is a prefix sequence
+    pub fn key(&self, action: &DesktopAction) -> Key {
+        match self.bindings.get(action).map(Vec::as_slice) {
+            Some([key]) => *key,
+            _ => Key::None,
+        }
+    }
+
+    /// The key is bound to an action on its own
+    pub fn is_bound(&self, key: Key) -> bool {
+        key != Key::None && self.bindings.values().any(|keys| keys[..] == [key])
+    }
+
+    /// The key starts a prefix sequence
+    pub fn is_prefix(&self, key: Key) -> bool {
+        key != Key::None && self.bindings.values().any(|keys| keys.len() == 2 && keys[0] == key)
+    }
+
+    /// Action bound to the prefix followed by the key. Pressing the prefix twice sends it, unless bound to something else
+    pub fn sequence_action(&self, prefix: Key, key: Key) -> Option<DesktopAction> {
+        let action = self.bindings
+            .iter()
+            .find(|(_, keys)| keys[..] == [prefix, key])
+            .map(|(action, _)| action.clone());
+
+        match action {
+            None if prefix == key => Some(DesktopAction::SendPrefix),
+            action => action
+        }
+    }
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        Self {
+            bindings: HashMap::from([
+                (DesktopAction::Detach, vec![Key::new(KeyCode::D, KeyModifier::Ctrl | KeyModifier::Alt)]),
+                (DesktopAction::KillWindow, vec![Key::new(KeyCode::K, KeyModifier::Ctrl | KeyModifier::Alt)]),
+                (DesktopAction::SwitchWindow, vec![Key::new(KeyCode::W, KeyModifier::Ctrl | KeyModifier::Alt)]),
+            ])
+        }
+    }
+}
+
+impl TryFrom<HashMap<String, String>> for KeyBindings {
+    type Error = anyhow::Error;
+
+    /// Override the default bindings, an empty binding or "None" removes it
+    fn try_from(value: HashMap<String, String>) -> Result<Self, Self::Error> {
+        let mut key_bindings = KeyBindings::default();
+
+        for (action, binding) in value {
+            let action = DesktopAction::from_str(&action)?;
+
+            match binding.trim() {
+                "" | "None" => key_bindings.bindings.remove(&action),
+                binding => key_bindings.bindings.insert(action, parse_key_sequence(binding)?),
+            };
+        }
+
+        Ok(key_bindings)
+    }
+}
+
+/// Parse a chord such as "Ctrl+Alt+K", or a prefix sequence such as "Ctrl+A N"
+pub fn parse_key_sequence(binding: &str) -> anyhow::Result<Vec<Key>> {
+    let keys = binding
+        .split_whitespace()
+        .map(parse_key)
+        .collect::<anyhow::Result<Vec<Key>>>()?;
+
+    match keys.len() {
+        1 | 2 => Ok(keys),
+        _ => Err(anyhow!("\"{binding}\" must be a chord, or a prefix chord followed by a key")),
+    }
+}
+
+/// Parse a chord such as "Ctrl+Alt+K", "Shift+F5" or "PageUp"
+pub fn parse_key(chord: &str) -> anyhow::Result<Key> {
+    let mut modifier = KeyModifier::None;
+    let mut parts: Vec<&str> = chord.split('+').map(str::trim).collect();
+
+    // "Ctrl++" is not supported, AppCUI has no key code for it
+    let name = parts.pop().filter(|name| !name.is_empty()).ok_or(anyhow!("Missing key in \"{chord}\""))?;
+
+    for part in parts {
+        match part.to_lowercase().as_str() {
+            "ctrl" | "control" => modifier |= KeyModifier::Ctrl,
+            "alt" => modifier |= KeyModifier::Alt,
+            "shift" => modifier |= KeyModifier::Shift,
+            _ => return Err(anyhow!("Unknown modifier \"{part}\" in \"{chord}\"")),
+        }
+    }
+
+    let code = (1..64u8)
+        .map(KeyCode::from)
+        .find(|code| code.name().eq_ignore_ascii_case(name))
+        .ok_or(anyhow!("Unknown key \"{name}\" in \"{chord}\""))?;
+
+    Ok(Key::new(code, modifier))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_keys() {
+        let cases: &[(&str, Key)] = &[
+            ("K", Key::new(KeyCode::K, KeyModifier::None)),
+            ("Ctrl+Alt+K", Key::new(KeyCode::K, KeyModifier::Ctrl | KeyModifier::Alt)),
+            ("control + shift + f5", Key::new(KeyCode::F5, KeyModifier::Ctrl | KeyModifier::Shift)),
+            ("PageUp", Key::new(KeyCode::PageUp, KeyModifier::None)),
+            ("Alt+Tab", Key::new(KeyCode::Tab, KeyModifier::Alt)),
+        ];
+
+        for (chord, expected) in cases {
+            assert_eq!(parse_key(chord).unwrap(), *expected, "{chord:?}");
+        }
+
+        for chord in ["", "Ctrl+", "Ctrl++", "Super+K", "Ctrl+Nope", "Ctrl Alt"] {
+            assert!(parse_key(chord).is_err(), "{chord:?}");
+        }
+    }
+
+    #[test]
+    fn parses_key_sequences() {
+        let prefix = Key::new(KeyCode::A, KeyModifier::Ctrl);
+
+        assert_eq!(parse_key_sequence("Ctrl+A").unwrap(), [prefix]);
+        assert_eq!(parse_key_sequence(" Ctrl+A  Shift+D ").unwrap(), [prefix, Key::new(KeyCode::D, KeyModifier::Shift)]);
+
+        for binding in ["", "Ctrl+A B C", "Ctrl+A Nope"] {
+            assert!(parse_key_sequence(binding).is_err(), "{binding:?}");
+        }
+    }
+
+    #[test]
+    fn parses_actions() {
+        let cases: &[(&str, DesktopAction)] = &[
+            ("exit", DesktopAction::Exit),
+            ("hide_window", DesktopAction::MinimizeWindow),
+            ("switch_window", DesktopAction::SwitchWindow),
+            ("open:Terminal", DesktopAction::OpenApp("Terminal".to_string())),
+            ("open: Text Editor ", DesktopAction::OpenApp("Text Editor".to_string())),
+            ("workspace:1", DesktopAction::Workspace(1)),
+            ("workspace:12", DesktopAction::Workspace(12)),
+            ("move_to_workspace:3", DesktopAction::MoveToWorkspace(3)),
+        ];
+
+        for (name, expected) in cases {
+            assert_eq!(DesktopAction::from_str(name).unwrap(), *expected, "{name:?}");
+        }
+
+        for name in ["", "Exit", "workspace:0", "workspace:", "workspace:-1", "workspace:two", "move_to_workspace:0", "open", "open:", "open: ", "unknown"] {
+            assert!(DesktopAction::from_str(name).is_err(), "{name:?}");
+        }
+    }
+
+    #[test]
+    fn overrides_defaults() {
+        let bindings = KeyBindings::try_from(HashMap::from([
+            ("kill_window".to_string(), "None".to_string()),
+            ("exit".to_string(), "Ctrl+A Q".to_string()),
+        ]))
+        .unwrap();
+        let prefix = Key::new(KeyCode::A, KeyModifier::Ctrl);
+        let q = Key::new(KeyCode::Q, KeyModifier::None);
+
+        assert_eq!(bindings.key(&DesktopAction::KillWindow), Key::None);
+        assert_eq!(bindings.key(&DesktopAction::Detach), Key::new(KeyCode::D, KeyModifier::Ctrl | KeyModifier::Alt));
+        assert_eq!(bindings.key(&DesktopAction::Exit), Key::None);
+        assert!(bindings.is_prefix(prefix));
+        assert!(!bindings.is_bound(prefix));
+        assert_eq!(bindings.sequence_action(prefix, q), Some(DesktopAction::Exit));
+        assert_eq!(bindings.sequence_action(prefix, prefix), Some(DesktopAction::SendPrefix));
+
+        let invalid = HashMap::from([("exit".to_string(), "Ctrl+A B C".to_string())]);
+        assert!(KeyBindings::try_from(invalid).is_err());
+    }
+
+    #[test]
+    fn matches_prefix_sequences() {
+        let bindings = KeyBindings::try_from(HashMap::from([
+            ("exit".to_string(), "Ctrl+B Q".to_string()),
+            ("split_right".to_string(), "Ctrl+B Ctrl+B".to_string()),
+            ("hide_window".to_string(), "Ctrl+Alt+H".to_string()),
+        ]))
+        .unwrap();
+        let prefix = Key::new(KeyCode::B, KeyModifier::Ctrl);
+        let hide = Key::new(KeyCode::H, KeyModifier::Ctrl | KeyModifier::Alt);
+
+        assert!(bindings.is_prefix(prefix));
+        assert!(!bindings.is_prefix(hide));
+        assert!(bindings.is_bound(hide));
+        assert!(!bindings.is_prefix(Key::None));
+        assert_eq!(bindings.key(&DesktopAction::MinimizeWindow), hide);
+
+        // The second key is matched with its modifiers, a prefix bound twice overrides sending it
+        assert_eq!(bindings.sequence_action(prefix, Key::new(KeyCode::Q, KeyModifier::None)), Some(DesktopAction::Exit));
+        assert_eq!(bindings.sequence_action(prefix, Key::new(KeyCode::Q, KeyModifier::Shift)), None);
+        assert_eq!(bindings.sequence_action(prefix, prefix), Some(DesktopAction::SplitRight));
+    }
+}
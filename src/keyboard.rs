@@ -1,3 +1,5 @@
+use crate::keybindings::DesktopAction;
+use crate::terminal_emulation::InputModes;
 use crate::tui_window::CustomKeyboardControl;
 use appcui::input::{Key, KeyModifier};
 use appcui::prelude::{EventProcessStatus, KeyCode, OnKeyPressed};
@@ -9,23 +11,46 @@ impl OnKeyPressed for CustomKeyboardControl {
             return EventProcessStatus::Ignored;
         }
 
-        if key.modifier == KeyModifier::Ctrl && key.code == KeyCode::C {
-            self.tx.send_blocking(Input::Terminate).ok();
-            self.should_exit = true;
-        }
-        else {
-            if let Some(data) = to_escape_sequence_vec(key, character) {
-                self.tx
-                    .send_blocking(Input::Data(data))
-                    .ok();
+        if let Some((prefix, prefix_character)) = self.pending_prefix.take() {
+            match self.key_bindings.sequence_action(prefix, key) {
+                Some(DesktopAction::SendPrefix) => self.send_key(prefix, prefix_character),
+                Some(action) => {
+                    self.action_tx.send_blocking(action).ok();
+                },
+                // Unknown sequences are dropped, like tmux does
+                None => {}
             }
+
+            return EventProcessStatus::Processed;
+        }
+
+        if self.key_bindings.is_prefix(key) {
+            self.pending_prefix = Some((key, character));
+            return EventProcessStatus::Processed;
+        }
+
+        // Let the desktop run the action bound to the key
+        if self.key_bindings.is_bound(key) {
+            return EventProcessStatus::Ignored;
         }
 
+        self.send_key(key, character);
+
         EventProcessStatus::Processed
     }
 }
 
-pub fn to_escape_sequence_vec(key: Key, character: char) -> Option<Vec<u8>> {
+impl CustomKeyboardControl {
+    fn send_key(&self, key: Key, character: char) {
+        if let Some(data) = to_escape_sequence_vec(key, character, self.input_modes) {
+            self.tx
+                .send_blocking(Input::Data(data))
+                .ok();
+        }
+    }
+}
+
+pub fn to_escape_sequence_vec(key: Key, character: char, input_modes: InputModes) -> Option<Vec<u8>> {
     use KeyModifier as KM;
 
     let mut seq = Vec::new();
@@ -43,6 +68,16 @@ pub fn to_escape_sequence_vec(key: Key, character: char) -> Option<Vec<u8>> {
         mod_param += 4;
     }
 
+    let enhanced = match input_modes.kitty_keyboard_flags {
+        0 if input_modes.modify_other_keys != 0 => modify_other_key(key, character, mod_param, input_modes.modify_other_keys),
+        0 => None,
+        flags => kitty_key(key, character, mod_param, flags),
+    };
+
+    if enhanced.is_some() {
+        return enhanced;
+    }
+
     match key.code {
         // ----- Single-byte ASCII -----
         KeyCode::Space => seq.push(b' '),
@@ -55,14 +90,14 @@ pub fn to_escape_sequence_vec(key: Key, character: char) -> Option<Vec<u8>> {
         KeyCode::Backspace => seq.push(0x7F),
 
         // ----- Arrows -----
-        KeyCode::Up => return Some(csi_mod(b"A", mod_param)),
-        KeyCode::Down => return Some(csi_mod(b"B", mod_param)),
-        KeyCode::Right => return Some(csi_mod(b"C", mod_param)),
-        KeyCode::Left => return Some(csi_mod(b"D", mod_param)),
+        KeyCode::Up => return Some(cursor_key(b"A", mod_param, input_modes)),
+        KeyCode::Down => return Some(cursor_key(b"B", mod_param, input_modes)),
+        KeyCode::Right => return Some(cursor_key(b"C", mod_param, input_modes)),
+        KeyCode::Left => return Some(cursor_key(b"D", mod_param, input_modes)),
 
         // ----- Navigation -----
-        KeyCode::Home => return Some(csi_mod(b"H", mod_param)),
-        KeyCode::End => return Some(csi_mod(b"F", mod_param)),
+        KeyCode::Home => return Some(cursor_key(b"H", mod_param, input_modes)),
+        KeyCode::End => return Some(cursor_key(b"F", mod_param, input_modes)),
         KeyCode::PageUp => return Some(csi_mod_tilde(5, mod_param)),
         KeyCode::PageDown => return Some(csi_mod_tilde(6, mod_param)),
         KeyCode::Insert => return Some(csi_mod_tilde(2, mod_param)),
@@ -88,7 +123,7 @@ pub fn to_escape_sequence_vec(key: Key, character: char) -> Option<Vec<u8>> {
         KeyCode::P | KeyCode::Q | KeyCode::R | KeyCode::S | KeyCode::T |
         KeyCode::U | KeyCode::V | KeyCode::W | KeyCode::X | KeyCode::Y | KeyCode::Z |
         KeyCode::N0 | KeyCode::N1 | KeyCode::N2 | KeyCode::N3 | KeyCode::N4 |
-        KeyCode::N5 | KeyCode::N6 | KeyCode::N7 | KeyCode::N8 | KeyCode::N9 | KeyCode::None => {
+        KeyCode::N5 | KeyCode::N6 | KeyCode::N7 | KeyCode::N8 | KeyCode::N9 => {
             let mut c = match key.code {
                 KeyCode::A => b'a', KeyCode::B => b'b', KeyCode::C => b'c', KeyCode::D => b'd', KeyCode::E => b'e',
                 KeyCode::F => b'f', KeyCode::G => b'g', KeyCode::H => b'h', KeyCode::I => b'i', KeyCode::J => b'j',
@@ -97,7 +132,7 @@ pub fn to_escape_sequence_vec(key: Key, character: char) -> Option<Vec<u8>> {
                 KeyCode::U => b'u', KeyCode::V => b'v', KeyCode::W => b'w', KeyCode::X => b'x', KeyCode::Y => b'y', KeyCode::Z => b'z',
                 KeyCode::N0 => b'0', KeyCode::N1 => b'1', KeyCode::N2 => b'2', KeyCode::N3 => b'3', KeyCode::N4 => b'4',
                 KeyCode::N5 => b'5', KeyCode::N6 => b'6', KeyCode::N7 => b'7', KeyCode::N8 => b'8', KeyCode::N9 => b'9',
-                _ => character as u8,
+                _ => unreachable!(),
             };
 
             if key.modifier.contains(KM::Shift) {
@@ -106,7 +141,7 @@ pub fn to_escape_sequence_vec(key: Key, character: char) -> Option<Vec<u8>> {
 
             if key.modifier.contains(KM::Ctrl) {
                 // Ctrl+A → 0x01, etc.
-                let ctrl = (c & 0x1F) as u8;
+                let ctrl = c & 0x1F;
                 seq.push(ctrl);
             }
             else {
@@ -121,11 +156,114 @@ pub fn to_escape_sequence_vec(key: Key, character: char) -> Option<Vec<u8>> {
 
             return Some(seq);
         }
+
+        // ----- Any other printable character, possibly outside ASCII -----
+        KeyCode::None => {
+            if character == '\0' {
+                return None;
+            }
+
+            if key.modifier.contains(KM::Alt) {
+                seq.push(0x1B);
+            }
+
+            if key.modifier.contains(KM::Ctrl) && character.is_ascii() {
+                seq.push(character as u8 & 0x1F);
+            }
+            else {
+                let mut buffer = [0; 4];
+                seq.extend_from_slice(character.encode_utf8(&mut buffer).as_bytes());
+            }
+        }
     }
 
     Some(seq)
 }
 
+/// Encode a key with the kitty keyboard protocol, or `None` when the legacy encoding is kept
+fn kitty_key(key: Key, character: char, mod_param: u8, flags: u8) -> Option<Vec<u8>> {
+    use KeyModifier as KM;
+
+    if flags & (InputModes::KITTY_DISAMBIGUATE | InputModes::KITTY_ALL_KEYS) == 0 {
+        return None;
+    }
+
+    let all_keys = flags & InputModes::KITTY_ALL_KEYS != 0;
+
+    let code = match key.code {
+        KeyCode::Escape => 27,
+        // Kept as-is so that the user can still type `reset` if the application crashes
+        KeyCode::Enter | KeyCode::Tab | KeyCode::Backspace if !all_keys && mod_param == 1 => return None,
+        KeyCode::Enter => 13,
+        KeyCode::Tab => 9,
+        KeyCode::Backspace => 127,
+        _ => {
+            let code = text_key_code(key, character)?;
+
+            // Text typed with or without Shift is still sent as text
+            if !all_keys && !key.modifier.contains(KM::Ctrl) && !key.modifier.contains(KM::Alt) {
+                return None;
+            }
+
+            code
+        }
+    };
+
+    if mod_param == 1 {
+        Some(format!("\x1B[{}u", code).into_bytes())
+    } else {
+        Some(format!("\x1B[{};{}u", code, mod_param).into_bytes())
+    }
+}
+
+/// Encode a modified key with xterm modifyOtherKeys, or `None` when the legacy encoding is kept
+fn modify_other_key(key: Key, character: char, mod_param: u8, level: u8) -> Option<Vec<u8>> {
+    use KeyModifier as KM;
+
+    if mod_param == 1 || key.modifier == KM::Shift {
+        return None;
+    }
+
+    let code = match key.code {
+        KeyCode::Escape => 27,
+        KeyCode::Enter => 13,
+        KeyCode::Tab => 9,
+        KeyCode::Backspace => 127,
+        _ => {
+            let code = text_key_code(key, character)?;
+
+            // Level 1 leaves out the keys that already have a well-known encoding
+            let ctrl_letter = key.modifier == KM::Ctrl && char::from_u32(code).is_some_and(|c| c.is_ascii_lowercase());
+            let alt_text = !key.modifier.contains(KM::Ctrl);
+            if level == 1 && (ctrl_letter || alt_text) {
+                return None;
+            }
+
+            code
+        }
+    };
+
+    Some(format!("\x1B[27;{};{}~", mod_param, code).into_bytes())
+}
+
+/// Unicode code point of the unshifted text key
+fn text_key_code(key: Key, character: char) -> Option<u32> {
+    let c = match key.code {
+        KeyCode::Space => ' ',
+        KeyCode::A => 'a', KeyCode::B => 'b', KeyCode::C => 'c', KeyCode::D => 'd', KeyCode::E => 'e',
+        KeyCode::F => 'f', KeyCode::G => 'g', KeyCode::H => 'h', KeyCode::I => 'i', KeyCode::J => 'j',
+        KeyCode::K => 'k', KeyCode::L => 'l', KeyCode::M => 'm', KeyCode::N => 'n', KeyCode::O => 'o',
+        KeyCode::P => 'p', KeyCode::Q => 'q', KeyCode::R => 'r', KeyCode::S => 's', KeyCode::T => 't',
+        KeyCode::U => 'u', KeyCode::V => 'v', KeyCode::W => 'w', KeyCode::X => 'x', KeyCode::Y => 'y', KeyCode::Z => 'z',
+        KeyCode::N0 => '0', KeyCode::N1 => '1', KeyCode::N2 => '2', KeyCode::N3 => '3', KeyCode::N4 => '4',
+        KeyCode::N5 => '5', KeyCode::N6 => '6', KeyCode::N7 => '7', KeyCode::N8 => '8', KeyCode::N9 => '9',
+        KeyCode::None if character != '\0' => character.to_lowercase().next().unwrap_or(character),
+        _ => return None,
+    };
+
+    Some(c as u32)
+}
+
 fn csi_mod(final_byte: &[u8], mod_param: u8) -> Vec<u8> {
     if mod_param == 1 {
         // No modifier
@@ -138,6 +276,17 @@ fn csi_mod(final_byte: &[u8], mod_param: u8) -> Vec<u8> {
     }
 }
 
+fn cursor_key(final_byte: &[u8], mod_param: u8, input_modes: InputModes) -> Vec<u8> {
+    if mod_param == 1 && input_modes.application_cursor_keys {
+        // Application cursor keys (DECCKM) use SS3
+        let mut v = vec![0x1B, b'O'];
+        v.extend_from_slice(final_byte);
+        v
+    } else {
+        csi_mod(final_byte, mod_param)
+    }
+}
+
 fn csi_mod_tilde(code: u8, mod_param: u8) -> Vec<u8> {
     if mod_param == 1 {
         format!("\x1B[{}~", code).into_bytes()
@@ -156,4 +305,95 @@ fn ss3_or_csi(final_byte: &[u8], base_code: u8, mod_param: u8) -> Vec<u8> {
         // CSI form with modifiers
         format!("\x1B[{};{}~", base_code, mod_param).into_bytes()
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::terminal_emulation::MouseTracking;
+
+    const NORMAL: InputModes = InputModes {
+        application_cursor_keys: false,
+        kitty_keyboard_flags: 0,
+        modify_other_keys: 0,
+        mouse_tracking: MouseTracking::None,
+        sgr_mouse: false,
+    };
+
+    const APPLICATION: InputModes = InputModes {
+        application_cursor_keys: true,
+        ..NORMAL
+    };
+
+    const KITTY: InputModes = InputModes {
+        kitty_keyboard_flags: InputModes::KITTY_DISAMBIGUATE,
+        ..NORMAL
+    };
+
+    const KITTY_ALL_KEYS: InputModes = InputModes {
+        kitty_keyboard_flags: InputModes::KITTY_DISAMBIGUATE | InputModes::KITTY_ALL_KEYS,
+        ..NORMAL
+    };
+
+    const MODIFY_OTHER_KEYS: InputModes = InputModes {
+        modify_other_keys: 2,
+        ..NORMAL
+    };
+
+    #[test]
+    fn encodes_keys() {
+        let cases: &[(KeyCode, KeyModifier, char, InputModes, &[u8])] = &[
+            // ASCII
+            (KeyCode::A, KeyModifier::None, 'a', NORMAL, b"a"),
+            (KeyCode::A, KeyModifier::Shift, 'A', NORMAL, b"A"),
+            (KeyCode::C, KeyModifier::Ctrl, 'c', NORMAL, b"\x03"),
+            (KeyCode::X, KeyModifier::Alt, 'x', NORMAL, b"\x1Bx"),
+            (KeyCode::None, KeyModifier::None, '!', NORMAL, b"!"),
+            (KeyCode::None, KeyModifier::Ctrl, '[', NORMAL, b"\x1B"),
+            // Unicode
+            (KeyCode::None, KeyModifier::None, 'é', NORMAL, "é".as_bytes()),
+            (KeyCode::None, KeyModifier::Shift, 'Ü', NORMAL, "Ü".as_bytes()),
+            (KeyCode::None, KeyModifier::None, 'ñ', NORMAL, "ñ".as_bytes()),
+            (KeyCode::None, KeyModifier::None, 'ж', NORMAL, "ж".as_bytes()),
+            (KeyCode::None, KeyModifier::None, '中', NORMAL, "中".as_bytes()),
+            (KeyCode::None, KeyModifier::None, '🦀', NORMAL, "🦀".as_bytes()),
+            (KeyCode::None, KeyModifier::Alt, 'é', NORMAL, "\x1Bé".as_bytes()),
+            (KeyCode::None, KeyModifier::Ctrl, 'é', NORMAL, "é".as_bytes()),
+            // Cursor keys
+            (KeyCode::Up, KeyModifier::None, '\0', NORMAL, b"\x1B[A"),
+            (KeyCode::Up, KeyModifier::None, '\0', APPLICATION, b"\x1BOA"),
+            (KeyCode::Left, KeyModifier::Ctrl, '\0', APPLICATION, b"\x1B[1;5D"),
+            (KeyCode::Home, KeyModifier::None, '\0', APPLICATION, b"\x1BOH"),
+            (KeyCode::End, KeyModifier::None, '\0', NORMAL, b"\x1B[F"),
+            // Kitty keyboard protocol
+            (KeyCode::I, KeyModifier::Ctrl, 'i', KITTY, b"\x1B[105;5u"),
+            (KeyCode::Tab, KeyModifier::None, '\t', KITTY, b"\t"),
+            (KeyCode::A, KeyModifier::Ctrl | KeyModifier::Shift, 'A', KITTY, b"\x1B[97;6u"),
+            (KeyCode::A, KeyModifier::Shift, 'A', KITTY, b"A"),
+            (KeyCode::Escape, KeyModifier::None, '\0', KITTY, b"\x1B[27u"),
+            (KeyCode::Enter, KeyModifier::Shift, '\0', KITTY, b"\x1B[13;2u"),
+            (KeyCode::None, KeyModifier::Alt, 'é', KITTY, b"\x1B[233;3u"),
+            (KeyCode::Up, KeyModifier::Ctrl, '\0', KITTY, b"\x1B[1;5A"),
+            (KeyCode::A, KeyModifier::None, 'a', KITTY_ALL_KEYS, b"\x1B[97u"),
+            (KeyCode::Enter, KeyModifier::None, '\0', KITTY_ALL_KEYS, b"\x1B[13u"),
+            // xterm modifyOtherKeys
+            (KeyCode::I, KeyModifier::Ctrl, 'i', MODIFY_OTHER_KEYS, b"\x1B[27;5;105~"),
+            (KeyCode::Enter, KeyModifier::Ctrl, '\0', MODIFY_OTHER_KEYS, b"\x1B[27;5;13~"),
+            (KeyCode::A, KeyModifier::Shift, 'A', MODIFY_OTHER_KEYS, b"A"),
+        ];
+
+        for (code, modifier, character, input_modes, expected) in cases {
+            let key = Key::new(*code, *modifier);
+            let encoded = to_escape_sequence_vec(key, *character, *input_modes);
+
+            assert_eq!(encoded.as_deref(), Some(*expected), "{key:?} {character:?}");
+        }
+    }
+
+    #[test]
+    fn ignores_empty_character() {
+        let key = Key::new(KeyCode::None, KeyModifier::None);
+
+        assert_eq!(to_escape_sequence_vec(key, '\0', NORMAL), None);
+    }
+}
@@ -1,25 +1,71 @@
 mod terminal_emulation;
 mod tui_window;
 mod keyboard;
+mod mouse;
 mod desktop;
 mod shortcut;
 mod utils;
 mod args;
+mod config;
+mod keybindings;
+mod window_switcher;
+mod tiling;
+mod panes;
+mod tab_bar;
+mod session;
+mod server;
+mod control;
+mod cli;
+mod watcher;
+mod placeholders;
 
 use std::process::exit;
 use crate::desktop::MyDesktop;
-use crate::shortcut::parse_shortcut_dir;
+use crate::shortcut::{missing_dirs, parse_shortcut_dirs, shortcut_dirs, ShadowedShortcut};
 use appcui::backend::Type;
 use appcui::prelude::{App, Theme};
 use appcui::system::Themes;
 use clap::Parser;
-use crate::args::Args;
+use crate::args::{Args, Command, RunArgs};
+use crate::config::parse_config_file;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     let args = Args::parse();
 
-    let desktop_shortcuts = parse_shortcut_dir(args.shortcut_dir)?;
+    let run_args = match args.command {
+        None => RunArgs {
+            shortcuts: args.shortcut_dir.into_iter().chain(args.run.shortcuts).collect(),
+            ..args.run
+        },
+        Some(Command::Run(run_args)) => run_args,
+        Some(Command::Validate { shortcut_dir, config }) => return cli::validate(&shortcut_dir, &parse_config_file(config)?.defaults),
+        Some(Command::List { shortcuts, config }) => return cli::list(&shortcut_dirs(shortcuts), &parse_config_file(config)?.defaults),
+        Some(Command::NewShortcut { dir }) => return cli::new_shortcut(&dir),
+        Some(Command::Attach { name, desktop_args }) => return server::attach(&name, desktop_args),
+        Some(Command::Detach { name }) => return server::detach(&name),
+        Some(Command::Ls) => {
+            for name in server::list()? {
+                println!("{name}");
+            }
+
+            return Ok(());
+        }
+        Some(Command::Server { name, desktop_args }) => return server::run_server(&name, desktop_args).await,
+        Some(Command::Ctl { name, socket, requests }) => {
+            let socket_path = socket.unwrap_or_else(|| control::default_control_socket_path(&name));
+            return control::ctl(&socket_path, requests);
+        }
+    };
+
+    let desktop_shortcut_dirs = shortcut_dirs(run_args.shortcuts);
+    let config = parse_config_file(run_args.config.clone())?;
+    let (desktop_shortcuts, shadowed_shortcuts) = parse_shortcut_dirs(&desktop_shortcut_dirs, &config.defaults)?;
+    let missing_shortcut_dirs = missing_dirs(&desktop_shortcut_dirs);
+    let control_socket = run_args.control_socket.unwrap_or_else(|| {
+        let name = std::env::var(server::SERVER_ENV).unwrap_or("default".to_string());
+        control::default_control_socket_path(&name)
+    });
 
     let theme = Theme::new(Themes::Default);
 
@@ -27,8 +73,21 @@ async fn main() -> anyhow::Result<()> {
     //theme.text.enphasized_2 = CharAttribute::new(Color::Red, Color::Green, CharFlags::None);
     //theme.desktop.character = Character::new(' ', Color::RGB(255, 255, 255), Color::RGB(85, 85, 85), CharFlags::None);
 
+    let mut desktop = MyDesktop::new(desktop_shortcuts, desktop_shortcut_dirs, config, run_args.config, run_args.restore, control_socket);
+
+    desktop.notify_shortcut_errors();
+
+    if !shadowed_shortcuts.is_empty() {
+        let report = shadowed_shortcuts.iter().map(ShadowedShortcut::describe).collect::<Vec<String>>().join("\n");
+        desktop.notify("Shadowed shortcuts", &report);
+    }
+
+    if !missing_shortcut_dirs.is_empty() {
+        desktop.notify("Missing shortcut folders", &missing_shortcut_dirs.join("\n"));
+    }
+
     let app = App::with_backend(Type::CrossTerm)
-        .desktop(MyDesktop::new(desktop_shortcuts))
+        .desktop(desktop)
         .app_bar()
         .theme(theme)
         .color_schema(false)
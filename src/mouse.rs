@@ -0,0 +1,171 @@
+use crate::terminal_emulation::{InputModes, MouseTracking};
+use crate::tui_window::CustomKeyboardControl;
+use appcui::input::{KeyModifier, MouseButton, MouseEvent, MouseWheelDirection};
+use appcui::prelude::{EventProcessStatus, OnMouseEvent, Point};
+use virtual_terminal::Input;
+
+/// Mouse event in the terms of the xterm protocol
+#[derive(Debug, Clone, Copy)]
+pub enum MouseAction {
+    Press(MouseButton),
+    Release(MouseButton),
+    /// Move while the button is pressed
+    Drag(MouseButton),
+    /// Move without a pressed button
+    Move,
+    Wheel(MouseWheelDirection),
+}
+
+impl OnMouseEvent for CustomKeyboardControl {
+    fn on_mouse_event(&mut self, event: &MouseEvent) -> EventProcessStatus {
+        if self.input_modes.mouse_tracking == MouseTracking::None {
+            return EventProcessStatus::Ignored;
+        }
+
+        let (actions, position, modifier) = match event {
+            MouseEvent::Over(point) => (vec![MouseAction::Move], *point, KeyModifier::None),
+            MouseEvent::Pressed(data) => (vec![MouseAction::Press(data.button)], Point::new(data.x, data.y), data.modifier),
+            MouseEvent::Released(data) => (vec![MouseAction::Release(data.button)], Point::new(data.x, data.y), data.modifier),
+            MouseEvent::Drag(data) => (vec![MouseAction::Drag(data.button)], Point::new(data.x, data.y), data.modifier),
+            // AppCUI sends the second click as a double click, without its release
+            MouseEvent::DoubleClick(data) => (
+                vec![MouseAction::Press(data.button), MouseAction::Release(data.button)],
+                Point::new(data.x, data.y),
+                data.modifier
+            ),
+            // The wheel has no position, it happens where the mouse was seen last
+            MouseEvent::Wheel(direction) => (vec![MouseAction::Wheel(*direction)], self.mouse_position, KeyModifier::None),
+            MouseEvent::Enter | MouseEvent::Leave => return EventProcessStatus::Ignored,
+        };
+
+        self.mouse_position = position;
+
+        for action in actions {
+            if let Some(data) = to_mouse_sequence(action, position, modifier, self.input_modes) {
+                self.tx.send_blocking(Input::Data(data)).ok();
+            }
+        }
+
+        EventProcessStatus::Processed
+    }
+}
+
+/// Report of the mouse event at the given cell, `None` when the tracking mode of the application leaves it out
+pub fn to_mouse_sequence(action: MouseAction, position: Point, modifier: KeyModifier, input_modes: InputModes) -> Option<Vec<u8>> {
+    let required_tracking = match action {
+        MouseAction::Press(_) => MouseTracking::X10,
+        MouseAction::Release(_) | MouseAction::Wheel(_) => MouseTracking::Normal,
+        MouseAction::Drag(_) => MouseTracking::ButtonEvent,
+        MouseAction::Move => MouseTracking::AnyEvent,
+    };
+
+    if input_modes.mouse_tracking < required_tracking || position.x < 0 || position.y < 0 {
+        return None;
+    }
+
+    let mut button = match action {
+        MouseAction::Press(button) | MouseAction::Drag(button) => button_code(button),
+        // Only the SGR encoding tells which button was released
+        MouseAction::Release(button) if input_modes.sgr_mouse => button_code(button),
+        MouseAction::Release(_) | MouseAction::Move => 3,
+        MouseAction::Wheel(MouseWheelDirection::Up) => 64,
+        MouseAction::Wheel(MouseWheelDirection::Down) => 65,
+        MouseAction::Wheel(MouseWheelDirection::Left) => 66,
+        MouseAction::Wheel(MouseWheelDirection::Right) => 67,
+    };
+
+    if matches!(action, MouseAction::Drag(_) | MouseAction::Move) {
+        button += 32;
+    }
+
+    if input_modes.mouse_tracking != MouseTracking::X10 {
+        if modifier.contains(KeyModifier::Shift) {
+            button += 4;
+        }
+        if modifier.contains(KeyModifier::Alt) {
+            button += 8;
+        }
+        if modifier.contains(KeyModifier::Ctrl) {
+            button += 16;
+        }
+    }
+
+    let (x, y) = (position.x as u32 + 1, position.y as u32 + 1);
+
+    if input_modes.sgr_mouse {
+        let final_byte = match action {
+            MouseAction::Release(_) => 'm',
+            _ => 'M',
+        };
+
+        return Some(format!("\x1B[<{button};{x};{y}{final_byte}").into_bytes());
+    }
+
+    // Each value is one byte offset by 32, cells past column or row 223 cannot be reported
+    let encode = |value: u32| u8::try_from(value + 32).ok();
+
+    Some(vec![0x1B, b'[', b'M', encode(button)?, encode(x)?, encode(y)?])
+}
+
+fn button_code(button: MouseButton) -> u32 {
+    match button {
+        MouseButton::Left => 0,
+        MouseButton::Center => 1,
+        MouseButton::Right => 2,
+        MouseButton::None => 3,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_mouse_events() {
+        let modes = |mouse_tracking: MouseTracking, sgr_mouse: bool| InputModes {
+            mouse_tracking,
+            sgr_mouse,
+            ..InputModes::default()
+        };
+        let left = MouseButton::Left;
+        let at = Point::new(4, 9);
+        let none = KeyModifier::None;
+
+        // (event, cell, modifiers, modes of the application, report)
+        type Case = (MouseAction, Point, KeyModifier, InputModes, Option<&'static [u8]>);
+
+        let cases: &[Case] = &[
+            (MouseAction::Press(left), at, none, modes(MouseTracking::None, true), None),
+            // Bytes offset by 32, coordinates from 1
+            (MouseAction::Press(left), at, none, modes(MouseTracking::Normal, false), Some(b"\x1B[M %*")),
+            (MouseAction::Press(MouseButton::Right), at, none, modes(MouseTracking::Normal, false), Some(b"\x1B[M\"%*")),
+            (MouseAction::Release(left), at, none, modes(MouseTracking::Normal, false), Some(b"\x1B[M#%*")),
+            (MouseAction::Press(left), Point::new(222, 0), none, modes(MouseTracking::Normal, false), Some(b"\x1B[M \xFF!")),
+            (MouseAction::Press(left), Point::new(223, 0), none, modes(MouseTracking::Normal, false), None),
+            (MouseAction::Press(left), Point::new(-1, 0), none, modes(MouseTracking::Normal, true), None),
+            // SGR
+            (MouseAction::Press(left), at, none, modes(MouseTracking::Normal, true), Some(b"\x1B[<0;5;10M")),
+            (MouseAction::Release(MouseButton::Center), at, none, modes(MouseTracking::Normal, true), Some(b"\x1B[<1;5;10m")),
+            (MouseAction::Press(left), Point::new(300, 400), none, modes(MouseTracking::Normal, true), Some(b"\x1B[<0;301;401M")),
+            (MouseAction::Wheel(MouseWheelDirection::Up), at, none, modes(MouseTracking::Normal, true), Some(b"\x1B[<64;5;10M")),
+            (MouseAction::Wheel(MouseWheelDirection::Down), at, none, modes(MouseTracking::Normal, true), Some(b"\x1B[<65;5;10M")),
+            (MouseAction::Press(left), at, KeyModifier::Ctrl | KeyModifier::Shift, modes(MouseTracking::Normal, true), Some(b"\x1B[<20;5;10M")),
+            // Each mode adds events
+            (MouseAction::Release(left), at, none, modes(MouseTracking::X10, true), None),
+            (MouseAction::Press(left), at, KeyModifier::Alt, modes(MouseTracking::X10, true), Some(b"\x1B[<0;5;10M")),
+            (MouseAction::Drag(left), at, none, modes(MouseTracking::Normal, true), None),
+            (MouseAction::Drag(left), at, none, modes(MouseTracking::ButtonEvent, true), Some(b"\x1B[<32;5;10M")),
+            (MouseAction::Move, at, none, modes(MouseTracking::ButtonEvent, true), None),
+            (MouseAction::Move, at, none, modes(MouseTracking::AnyEvent, true), Some(b"\x1B[<35;5;10M")),
+            (MouseAction::Drag(left), at, none, modes(MouseTracking::AnyEvent, false), Some(b"\x1B[M@%*")),
+        ];
+
+        for (action, position, modifier, input_modes, expected) in cases {
+            assert_eq!(
+                to_mouse_sequence(*action, *position, *modifier, *input_modes).as_deref(),
+                *expected,
+                "{action:?} at {position:?} with {input_modes:?}"
+            );
+        }
+    }
+}
@@ -0,0 +1,296 @@
+use appcui::prelude::Rect;
+use serde::{Deserialize, Serialize};
+
+/// Step used to grow or shrink a pane
+pub const PANE_RATIO_STEP: f32 = 0.05;
+
+/// Side of a pane where a new pane is opened
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SplitDirection {
+    /// Side by side
+    Right,
+    /// Stacked
+    Down,
+}
+
+/// Direction used to move the focus between panes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PaneDirection {
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+/// Splits of a window, the leaves are pane ids
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PaneTree {
+    Pane(usize),
+    Split {
+        direction: SplitDirection,
+        /// Part of the space taken by the first child
+        ratio: f32,
+        first: Box<PaneTree>,
+        second: Box<PaneTree>,
+    },
+}
+
+impl PaneTree {
+    pub fn contains(&self, pane: usize) -> bool {
+        match self {
+            PaneTree::Pane(id) => *id == pane,
+            PaneTree::Split { first, second, .. } => first.contains(pane) || second.contains(pane),
+        }
+    }
+
+    /// Share the space of the pane with a new pane placed after it
+    pub fn split(&mut self, pane: usize, new_pane: usize, direction: SplitDirection, ratio: f32) -> bool {
+        match self {
+            PaneTree::Pane(id) if *id == pane => {
+                *self = PaneTree::Split {
+                    direction,
+                    ratio: ratio.clamp(0.1, 0.9),
+                    first: Box::new(PaneTree::Pane(pane)),
+                    second: Box::new(PaneTree::Pane(new_pane)),
+                };
+                true
+            }
+            PaneTree::Pane(_) => false,
+            PaneTree::Split { first, second, .. } => first.split(pane, new_pane, direction, ratio) || second.split(pane, new_pane, direction, ratio),
+        }
+    }
+
+    /// Remove the pane, its sibling takes its space. The last pane cannot be removed
+    pub fn remove(&mut self, pane: usize) -> bool {
+        let PaneTree::Split { first, second, .. } = self else {
+            return false;
+        };
+
+        if **first == PaneTree::Pane(pane) {
+            *self = std::mem::replace(second.as_mut(), PaneTree::Pane(pane));
+            return true;
+        }
+
+        if **second == PaneTree::Pane(pane) {
+            *self = std::mem::replace(first.as_mut(), PaneTree::Pane(pane));
+            return true;
+        }
+
+        first.remove(pane) || second.remove(pane)
+    }
+
+    /// Grow the pane by moving the border of its closest split, a negative delta shrinks it
+    pub fn resize(&mut self, pane: usize, delta: f32) -> bool {
+        let PaneTree::Split { ratio, first, second, .. } = self else {
+            return false;
+        };
+
+        if first.contains(pane) {
+            if !first.resize(pane, delta) {
+                *ratio = (*ratio + delta).clamp(0.1, 0.9);
+            }
+            return true;
+        }
+
+        if second.contains(pane) {
+            if !second.resize(pane, delta) {
+                *ratio = (*ratio - delta).clamp(0.1, 0.9);
+            }
+            return true;
+        }
+
+        false
+    }
+
+    /// Same splits with the pane ids changed by `new_id`
+    pub fn renumber(&self, new_id: &impl Fn(usize) -> usize) -> PaneTree {
+        match self {
+            PaneTree::Pane(id) => PaneTree::Pane(new_id(*id)),
+            PaneTree::Split { direction, ratio, first, second } => PaneTree::Split {
+                direction: *direction,
+                ratio: *ratio,
+                first: Box::new(first.renumber(new_id)),
+                second: Box::new(second.renumber(new_id)),
+            },
+        }
+    }
+
+    /// Positions of the panes in the area, the children of a split are separated by one empty cell
+    pub fn layout(&self, area: Rect) -> Vec<(usize, Rect)> {
+        let mut positions = Vec::new();
+        self.layout_into(area, &mut positions);
+        positions
+    }
+
+    fn layout_into(&self, area: Rect, positions: &mut Vec<(usize, Rect)>) {
+        match self {
+            PaneTree::Pane(id) => positions.push((*id, area)),
+            PaneTree::Split { direction, ratio, first, second } => {
+                let (first_area, second_area) = match direction {
+                    SplitDirection::Right => {
+                        let width = first_size(area.width(), *ratio);
+                        (
+                            Rect::new(area.left(), area.top(), area.left() + width - 1, area.bottom()),
+                            Rect::new((area.left() + width + 1).min(area.right()), area.top(), area.right(), area.bottom()),
+                        )
+                    }
+                    SplitDirection::Down => {
+                        let height = first_size(area.height(), *ratio);
+                        (
+                            Rect::new(area.left(), area.top(), area.right(), area.top() + height - 1),
+                            Rect::new(area.left(), (area.top() + height + 1).min(area.bottom()), area.right(), area.bottom()),
+                        )
+                    }
+                };
+
+                first.layout_into(first_area, positions);
+                second.layout_into(second_area, positions);
+            }
+        }
+    }
+}
+
+/// Size of the first child of a split, leaving at least one cell for the border and one for the second child
+fn first_size(size: u32, ratio: f32) -> i32 {
+    let available = size as i32 - 1;
+    ((available as f32 * ratio) as i32).clamp(1, (available - 1).max(1))
+}
+
+/// Closest pane in the direction that overlaps the pane on the other axis
+pub fn neighbour(layout: &[(usize, Rect)], pane: usize, direction: PaneDirection) -> Option<usize> {
+    let (_, current) = layout.iter().find(|(id, _)| *id == pane)?;
+
+    layout
+        .iter()
+        .filter(|(id, _)| *id != pane)
+        .filter_map(|(id, rect)| {
+            let (distance, overlap) = match direction {
+                PaneDirection::Left => (current.left() - rect.right(), overlap(current.top(), current.bottom(), rect.top(), rect.bottom())),
+                PaneDirection::Right => (rect.left() - current.right(), overlap(current.top(), current.bottom(), rect.top(), rect.bottom())),
+                PaneDirection::Up => (current.top() - rect.bottom(), overlap(current.left(), current.right(), rect.left(), rect.right())),
+                PaneDirection::Down => (rect.top() - current.bottom(), overlap(current.left(), current.right(), rect.left(), rect.right())),
+            };
+
+            (distance > 0 && overlap > 0).then_some((*id, distance, overlap))
+        })
+        .min_by_key(|(_, distance, overlap)| (*distance, -overlap))
+        .map(|(id, _, _)| id)
+}
+
+fn overlap(start: i32, end: i32, other_start: i32, other_end: i32) -> i32 {
+    end.min(other_end) - start.max(other_start) + 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Pane 0 on the left, panes 1 and 2 stacked on the right
+    fn tree() -> PaneTree {
+        let mut tree = PaneTree::Pane(0);
+        assert!(tree.split(0, 1, SplitDirection::Right, 0.5));
+        assert!(tree.split(1, 2, SplitDirection::Down, 0.5));
+        tree
+    }
+
+    fn ids(tree: &PaneTree) -> Vec<usize> {
+        tree.layout(Rect::new(0, 0, 79, 23)).into_iter().map(|(id, _)| id).collect()
+    }
+
+    #[test]
+    fn splits_and_removes_panes() {
+        let mut tree = tree();
+        assert_eq!(ids(&tree), [0, 1, 2]);
+        assert!(!tree.split(7, 3, SplitDirection::Right, 0.5));
+
+        assert!(tree.remove(1));
+        assert_eq!(ids(&tree), [0, 2]);
+        assert!(!tree.contains(1));
+        assert!(!tree.remove(1));
+
+        assert!(tree.remove(0));
+        assert_eq!(tree, PaneTree::Pane(2));
+        assert!(!tree.remove(2));
+    }
+
+    #[test]
+    fn clamps_ratios() {
+        let mut tree = PaneTree::Pane(0);
+        tree.split(0, 1, SplitDirection::Right, 2.0);
+        assert!(matches!(tree, PaneTree::Split { ratio, .. } if ratio == 0.9));
+
+        assert!(tree.resize(1, 1.0));
+        assert!(matches!(tree, PaneTree::Split { ratio, .. } if ratio == 0.1));
+        assert!(!tree.resize(7, 0.1));
+    }
+
+    #[test]
+    fn resizes_closest_split() {
+        let mut tree = tree();
+        assert!(tree.resize(2, PANE_RATIO_STEP));
+
+        let PaneTree::Split { ratio, second, .. } = &tree else {
+            panic!("{tree:?}");
+        };
+        assert_eq!(*ratio, 0.5);
+        assert!(matches!(**second, PaneTree::Split { ratio, .. } if ratio == 0.5 - PANE_RATIO_STEP));
+    }
+
+    #[test]
+    fn renumbers_panes() {
+        let tree = tree().renumber(&|id| id + 10);
+
+        assert_eq!(ids(&tree), [10, 11, 12]);
+    }
+
+    #[test]
+    fn lays_out_panes_with_borders() {
+        let layout = tree().layout(Rect::new(0, 0, 79, 23));
+
+        assert_eq!(layout, [
+            (0, Rect::new(0, 0, 38, 23)),
+            (1, Rect::new(40, 0, 79, 10)),
+            (2, Rect::new(40, 12, 79, 23)),
+        ]);
+    }
+
+    #[test]
+    fn keeps_panes_in_small_areas() {
+        let mut tree = tree();
+        tree.split(2, 3, SplitDirection::Right, 0.5);
+        tree.split(0, 4, SplitDirection::Down, 0.5);
+
+        for (width, height) in [(1, 1), (2, 1), (1, 2), (3, 3), (5, 4)] {
+            let area = Rect::new(2, 1, 2 + width - 1, 1 + height - 1);
+            let layout = tree.layout(area);
+
+            assert_eq!(layout.len(), 5);
+            for (id, rect) in layout {
+                let inside = rect.left() >= area.left() && rect.right() <= area.right() && rect.top() >= area.top() && rect.bottom() <= area.bottom();
+                assert!(inside, "{area:?}: pane {id} at {rect:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn finds_neighbours() {
+        let layout = tree().layout(Rect::new(0, 0, 79, 23));
+        let cases = [
+            (0, PaneDirection::Right, Some(2)),
+            (0, PaneDirection::Left, None),
+            (0, PaneDirection::Up, None),
+            (1, PaneDirection::Left, Some(0)),
+            (1, PaneDirection::Down, Some(2)),
+            (2, PaneDirection::Up, Some(1)),
+            (2, PaneDirection::Left, Some(0)),
+            (2, PaneDirection::Right, None),
+            (7, PaneDirection::Left, None),
+        ];
+
+        for (pane, direction, expected) in cases {
+            assert_eq!(neighbour(&layout, pane, direction), expected, "{pane} {direction:?}");
+        }
+    }
+}
@@ -0,0 +1,558 @@
+use crate::session::state_dir;
+use crate::utils::{expand_vars, home_dir, shell_quote};
+use anyhow::anyhow;
+use appcui::dialogs::{Location, OpenFileDialogFlags, SelectFolderDialogFlags};
+use appcui::prelude::window::Flags;
+use appcui::prelude::*;
+use appcui::system::Clipboard;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::path::{self, Path, PathBuf};
+
+/// Value asked to the user, or read, when a command is started. Written `<NAME:parameter:parameter>` in the program or its arguments
+#[derive(Clone, Debug, PartialEq)]
+enum Placeholder {
+    /// `<FILE_PATH>`, `<FILE_PATH:*.toml>` or `<FILE_PATH:*.toml:~/projects>`
+    FilePath { filter: Option<String>, dir: Option<String> },
+    /// `<FILES>`, with the same parameters, gives one argument per selected file
+    Files { filter: Option<String>, dir: Option<String> },
+    /// `<FOLDER_PATH>` or `<FOLDER_PATH:~/projects>`
+    FolderPath { dir: Option<String> },
+    /// `<INPUT:Host name>`
+    Input { label: String },
+    /// `<SECRET:Password>`, typed without being shown and never remembered
+    Secret { label: String },
+    /// `<CHOICE:a|b|c>`
+    Choice { choices: Vec<String> },
+    /// `<ENV:VAR>`, empty when the variable is not set
+    Env { name: String },
+    /// `<CLIPBOARD>`
+    Clipboard,
+}
+
+/// Answers of the last launches by placeholder text, and the folder of the last file or folder selected
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+struct DialogMemory {
+    last_folder: Option<PathBuf>,
+    #[serde(default)]
+    answers: BTreeMap<String, Vec<String>>,
+}
+
+/// Error of a dialog closed without an answer, which is not a failure
+#[derive(Debug)]
+pub struct Cancelled(pub String);
+
+impl Display for Cancelled {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Error for Cancelled {}
+
+impl Placeholder {
+    /// Placeholder written between `<` and `>`, other text is left as it is
+    fn parse(content: &str) -> Option<Self> {
+        let (name, parameters) = content.split_once(':').unwrap_or((content, ""));
+        // An empty filter keeps the folder in second position
+        let mut parameters = parameters.splitn(2, ':').map(|parameter| (!parameter.is_empty()).then(|| parameter.to_string()));
+
+        match name {
+            "FILE_PATH" => Some(Self::FilePath { filter: parameters.next().flatten(), dir: parameters.next().flatten() }),
+            "FILES" => Some(Self::Files { filter: parameters.next().flatten(), dir: parameters.next().flatten() }),
+            // The folder may contain colons
+            "FOLDER_PATH" => Some(Self::FolderPath { dir: content.split_once(':').map(|(_, dir)| dir.to_string()).filter(|dir| !dir.is_empty()) }),
+            "INPUT" if content.len() > name.len() + 1 => Some(Self::Input { label: content[name.len() + 1..].to_string() }),
+            "SECRET" if content.len() > name.len() + 1 => Some(Self::Secret { label: content[name.len() + 1..].to_string() }),
+            "CHOICE" if content.len() > name.len() + 1 => Some(Self::Choice { choices: content[name.len() + 1..].split('|').map(str::to_string).collect() }),
+            "ENV" if content.len() > name.len() + 1 => Some(Self::Env { name: content[name.len() + 1..].to_string() }),
+            "CLIPBOARD" if content == name => Some(Self::Clipboard),
+            _ => None,
+        }
+    }
+
+    /// Ask or read the value, `last` is the answer of the previous launch
+    fn resolve(&self, last: Option<&Vec<String>>, dialogs: &mut Dialogs) -> anyhow::Result<Vec<String>> {
+        let last_path = last.and_then(|values| values.first()).map(PathBuf::from);
+
+        match self {
+            Self::FilePath { filter, dir } => {
+                let file_name = last_path.as_deref().and_then(Path::file_name).map(|file_name| file_name.to_string_lossy().to_string()).unwrap_or_default();
+                let location = dialogs.start_dir(last_path.as_deref().and_then(Path::parent), dir.as_deref());
+
+                let file_path = dialogs.select_file("Select file", &file_name, filter.as_deref(), &location)?
+                    .ok_or(Cancelled("No file selected".to_string()))?;
+
+                Ok(vec![file_path])
+            }
+            Self::Files { filter, dir } => {
+                let mut files = Vec::new();
+
+                // The file dialog selects a single file, it is shown again until the user is done
+                loop {
+                    let location = match files.is_empty() {
+                        true => dialogs.start_dir(last_path.as_deref().and_then(Path::parent), dir.as_deref()),
+                        false => dialogs.start_dir(None, None),
+                    };
+
+                    let Some(file_path) = dialogs.select_file(&format!("Select file {}", files.len() + 1), "", filter.as_deref(), &location)? else {
+                        break;
+                    };
+
+                    files.push(file_path);
+
+                    if !dialogs::proceed("Select files", "Add another file?") {
+                        break;
+                    }
+                }
+
+                match files.is_empty() {
+                    true => Err(Cancelled("No file selected".to_string()).into()),
+                    false => Ok(files),
+                }
+            }
+            Self::FolderPath { dir } => {
+                let location = dialogs.start_dir(last_path.as_deref(), dir.as_deref());
+
+                let folder_path = dialogs::select_folder("Select folder", Location::Path(&location), SelectFolderDialogFlags::Icons)
+                    .ok_or(Cancelled("No folder selected".to_string()))?;
+
+                dialogs.memory.last_folder = Some(PathBuf::from(normalized(&folder_path)));
+
+                Ok(vec![normalized(&folder_path)])
+            }
+            Self::Input { label } => {
+                let last_value = last.and_then(|values| values.first()).cloned();
+
+                match dialogs::input::<String>("Input", label, last_value, None) {
+                    None => Err(Cancelled(format!("No value entered for \"{label}\"")).into()),
+                    Some(value) => Ok(vec![value]),
+                }
+            }
+            Self::Secret { label } => match SecretDialog::new(label).show() {
+                None => Err(Cancelled(format!("No value entered for \"{label}\"")).into()),
+                Some(value) => Ok(vec![value]),
+            },
+            Self::Choice { choices } => {
+                let selected = last
+                    .and_then(|values| values.first())
+                    .and_then(|last_choice| choices.iter().position(|choice| choice == last_choice))
+                    .unwrap_or(0);
+
+                match ChoiceDialog::new(choices, selected).show() {
+                    None => Err(Cancelled(format!("No choice made between {}", choices.join(", "))).into()),
+                    Some(index) => Ok(vec![choices[index].clone()]),
+                }
+            }
+            Self::Env { name } => Ok(vec![std::env::var(name).unwrap_or_default()]),
+            Self::Clipboard => match Clipboard::text() {
+                None => Err(anyhow!("The clipboard holds no text")),
+                Some(text) => Ok(vec![text]),
+            },
+        }
+    }
+
+    /// Answers worth proposing again on the next launch, the environment and the clipboard are read again
+    fn remembered(&self) -> bool {
+        !matches!(self, Self::Secret { .. } | Self::Env { .. } | Self::Clipboard)
+    }
+}
+
+/// Dialogs shown while the placeholders of a command are replaced
+struct Dialogs<'a> {
+    memory: DialogMemory,
+    /// `dialog_dir` of the shortcut
+    dialog_dir: Option<&'a str>,
+}
+
+impl Dialogs<'_> {
+    /// First existing folder among the folder of the previous answer, the folder of the placeholder, the `dialog_dir` of the shortcut,
+    /// the last folder used by a dialog, and the home directory. Relative folders start from the current directory
+    fn start_dir(&self, last_folder: Option<&Path>, dir: Option<&str>) -> PathBuf {
+        last_folder
+            .map(Path::to_path_buf)
+            .into_iter()
+            .chain(dir.map(|dir| PathBuf::from(expand_vars(dir))))
+            .chain(self.dialog_dir.map(|dialog_dir| PathBuf::from(expand_vars(dialog_dir))))
+            .chain(self.memory.last_folder.clone())
+            .filter_map(|folder| path::absolute(folder).ok())
+            .find(|folder| folder.is_dir())
+            .unwrap_or_else(home_dir)
+    }
+
+    /// Path chosen in the file dialog, none when it was cancelled
+    fn select_file(&mut self, title: &str, file_name: &str, filter: Option<&str>, location: &Path) -> anyhow::Result<Option<String>> {
+        let extension_mask = filter.map(extension_mask).transpose()?;
+
+        let Some(file_path) = dialogs::open(
+            title,
+            file_name,
+            Location::Path(location),
+            extension_mask.as_deref(),
+            OpenFileDialogFlags::Icons | OpenFileDialogFlags::CheckIfFileExists
+        ) else {
+            return Ok(None);
+        };
+
+        let file_path = normalized(&file_path);
+        self.memory.last_folder = Path::new(&file_path).parent().map(Path::to_path_buf);
+
+        Ok(Some(file_path))
+    }
+}
+
+/// Replace the placeholders of the program and its arguments, each one is asked once.
+/// An argument that is only `<FILES>` becomes one argument per file, elsewhere the files are separated by spaces.
+/// For a shell command line, each value is quoted so that it is never run as shell syntax.
+/// A dialog closed without an answer gives a [`Cancelled`] error
+pub fn replace_placeholders(program: &str, args: &[String], dialog_dir: Option<&str>, shell: bool) -> anyhow::Result<(String, Vec<String>)> {
+    let mut dialogs = Dialogs {
+        memory: load_dialog_memory(),
+        dialog_dir,
+    };
+    let initial_memory = dialogs.memory.clone();
+    let mut answers = BTreeMap::new();
+
+    let replaced = replace_in(program, shell, &mut dialogs, &mut answers).and_then(|program| {
+        let mut replaced_args = Vec::new();
+
+        for arg in args {
+            replaced_args.extend(replace_in(arg, shell, &mut dialogs, &mut answers)?);
+        }
+
+        Ok((program.join(" "), replaced_args))
+    });
+
+    // The answers given before a cancelled placeholder are also kept
+    let mut memory = dialogs.memory;
+    memory.answers.extend(answers);
+    memory.answers.retain(|text, _| is_remembered(text));
+
+    if memory.answers != initial_memory.answers || memory.last_folder != initial_memory.last_folder {
+        save_dialog_memory(&memory).ok();
+    }
+
+    replaced
+}
+
+/// Text with its placeholders replaced, as several parts when the only placeholder of the text has several values
+fn replace_in(text: &str, shell: bool, dialogs: &mut Dialogs, answers: &mut BTreeMap<String, Vec<String>>) -> anyhow::Result<Vec<String>> {
+    let mut parts = vec![String::new()];
+    let mut rest = text;
+
+    while let Some(start) = rest.find('<') {
+        let Some(length) = rest[start..].find('>') else {
+            break;
+        };
+
+        let placeholder_text = &rest[start..start + length + 1];
+        parts.last_mut().unwrap().push_str(&rest[..start]);
+        rest = &rest[start + length + 1..];
+
+        let Some(placeholder) = Placeholder::parse(&placeholder_text[1..placeholder_text.len() - 1]) else {
+            parts.last_mut().unwrap().push_str(placeholder_text);
+            continue;
+        };
+
+        if !answers.contains_key(placeholder_text) {
+            let last = dialogs.memory.answers.get(placeholder_text).cloned();
+            let answer = placeholder.resolve(last.as_ref(), dialogs)?;
+            answers.insert(placeholder_text.to_string(), answer);
+        }
+
+        let answer = match shell {
+            true => answers[placeholder_text].iter().map(|value| shell_quote(value)).collect(),
+            false => answers[placeholder_text].clone(),
+        };
+
+        match text == placeholder_text {
+            true => parts = answer,
+            false => parts.last_mut().unwrap().push_str(&answer.join(" ")),
+        }
+    }
+
+    parts.last_mut().unwrap().push_str(rest);
+
+    Ok(parts)
+}
+
+/// Whether the answer of a placeholder text such as `<INPUT:Host>` is saved
+fn is_remembered(text: &str) -> bool {
+    text.strip_prefix('<')
+        .and_then(|text| text.strip_suffix('>'))
+        .and_then(Placeholder::parse)
+        .is_some_and(|placeholder| placeholder.remembered())
+}
+
+/// The dialogs give paths such as `//tmp/file`
+fn normalized(path: &Path) -> String {
+    path.components().collect::<PathBuf>().to_string_lossy().to_string()
+}
+
+/// Mask of the file dialog from a filter such as `*.toml` or `*.yml,*.yaml`
+fn extension_mask(filter: &str) -> anyhow::Result<String> {
+    let extensions = filter
+        .split([',', ';', ' '])
+        .filter(|pattern| !pattern.is_empty())
+        .map(|pattern| {
+            let extension = pattern.trim_start_matches('*').trim_start_matches('.');
+
+            // The dialog panics on other characters
+            match !extension.is_empty() && extension.chars().all(|character| character.is_ascii_alphanumeric() || matches!(character, '_' | '-' | '.')) {
+                true => Ok(extension),
+                false => Err(anyhow!("\"{pattern}\" is not a file filter, use patterns such as *.toml")),
+            }
+        })
+        .collect::<anyhow::Result<Vec<&str>>>()?;
+
+    Ok(format!("Files = [{}]", extensions.join(", ")))
+}
+
+/// `$XDG_STATE_HOME/desktop-tui/placeholders.toml`, or `~/.local/state/desktop-tui/placeholders.toml`
+fn dialog_memory_path() -> Option<PathBuf> {
+    Some(state_dir()?.join("placeholders.toml"))
+}
+
+/// Answers of the last launches, none when the file cannot be read
+fn load_dialog_memory() -> DialogMemory {
+    dialog_memory_path()
+        .and_then(|memory_path| fs::read_to_string(memory_path).ok())
+        .and_then(|file_content| toml::from_str(&file_content).ok())
+        .unwrap_or_default()
+}
+
+fn save_dialog_memory(memory: &DialogMemory) -> anyhow::Result<()> {
+    let Some(memory_path) = dialog_memory_path() else {
+        return Ok(());
+    };
+
+    if let Some(parent) = memory_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    // Only readable by the user, the mode is only given to new files
+    let mut file = OpenOptions::new().write(true).create(true).truncate(true).mode(0o600).open(memory_path)?;
+    file.set_permissions(fs::Permissions::from_mode(0o600))?;
+    file.write_all(toml::to_string(memory)?.as_bytes())?;
+
+    Ok(())
+}
+
+/// List of the choices of a `<CHOICE:...>` placeholder, Enter picks the selected one
+#[ModalWindow(events = WindowEvents, response = usize)]
+struct ChoiceDialog {
+    list: Handle<ListBox>,
+}
+
+impl ChoiceDialog {
+    fn new(choices: &[String], selected: usize) -> Self {
+        let width = choices.iter().map(|choice| choice.chars().count()).max().unwrap_or(0).max(20) + 4;
+        let height = choices.len().min(15) + 2;
+
+        let mut dialog = Self {
+            base: ModalWindow::new(
+                "Choose",
+                LayoutBuilder::new()
+                    .alignment(Alignment::Center)
+                    .width(width as u32)
+                    .height(height as u32)
+                    .build(),
+                Flags::None
+            ),
+            list: Handle::None,
+        };
+
+        let mut list = ListBox::new(Layout::fill(), listbox::Flags::ScrollBars);
+        for choice in choices {
+            list.add(choice);
+        }
+        list.set_index(selected);
+
+        dialog.list = dialog.add(list);
+
+        dialog
+    }
+}
+
+impl WindowEvents for ChoiceDialog {
+    fn on_accept(&mut self) {
+        let selected = self.control(self.list).map_or(0, |list| list.index());
+        self.exit_with(selected);
+    }
+}
+
+/// Masked text field of a `<SECRET:...>` placeholder, Enter gives the typed text
+#[ModalWindow(events = PasswordEvents, response = String)]
+struct SecretDialog {
+    password: Handle<Password>,
+}
+
+impl SecretDialog {
+    fn new(label: &str) -> Self {
+        let width = label.chars().count().max(30) + 4;
+
+        let mut dialog = Self {
+            base: ModalWindow::new(
+                "Input",
+                LayoutBuilder::new()
+                    .alignment(Alignment::Center)
+                    .width(width as u32)
+                    .height(7)
+                    .build(),
+                Flags::None
+            ),
+            password: Handle::None,
+        };
+
+        dialog.add(Label::new(label, LayoutBuilder::new().x(1).y(1).width(1.0).height(1).build()));
+        dialog.password = dialog.add(Password::new(LayoutBuilder::new().x(1).y(3).width(1.0).height(1).build()));
+
+        dialog
+    }
+}
+
+impl PasswordEvents for SecretDialog {
+    fn on_accept(&mut self, handle: Handle<Password>) -> EventProcessStatus {
+        let value = self.control(handle).map(|password| password.password().to_string()).unwrap_or_default();
+        self.exit_with(value);
+        EventProcessStatus::Processed
+    }
+
+    fn on_cancel(&mut self, _handle: Handle<Password>) -> EventProcessStatus {
+        self.exit();
+        EventProcessStatus::Processed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, process};
+
+    fn replace(text: &str, shell: bool, answers: &[(&str, &[&str])]) -> Vec<String> {
+        let mut dialogs = Dialogs {
+            memory: DialogMemory::default(),
+            dialog_dir: None,
+        };
+        let mut answers = answers
+            .iter()
+            .map(|(placeholder, values)| (placeholder.to_string(), values.iter().map(|value| value.to_string()).collect()))
+            .collect();
+
+        replace_in(text, shell, &mut dialogs, &mut answers).unwrap()
+    }
+
+    #[test]
+    fn quotes_shell_values() {
+        let answers: &[(&str, &[&str])] = &[
+            ("<INPUT:Host>", &["example.org; rm -rf ~"]),
+            ("<FILES>", &["a b.txt", "it's.txt"]),
+        ];
+
+        assert_eq!(replace("ssh <INPUT:Host> | tee log", true, answers), ["ssh 'example.org; rm -rf ~' | tee log"]);
+        assert_eq!(replace("<FILES>", true, answers), ["'a b.txt'", r"'it'\''s.txt'"]);
+        assert_eq!(replace("<INPUT:Host>", false, answers), ["example.org; rm -rf ~"]);
+    }
+
+    #[test]
+    fn parses_placeholders() {
+        let cases: &[(&str, Option<Placeholder>)] = &[
+            ("FILE_PATH", Some(Placeholder::FilePath { filter: None, dir: None })),
+            ("FILE_PATH:*.toml:~/a:b", Some(Placeholder::FilePath { filter: Some("*.toml".to_string()), dir: Some("~/a:b".to_string()) })),
+            ("FILES::~/src", Some(Placeholder::Files { filter: None, dir: Some("~/src".to_string()) })),
+            ("FOLDER_PATH", Some(Placeholder::FolderPath { dir: None })),
+            ("FOLDER_PATH:/mnt/c:/Users", Some(Placeholder::FolderPath { dir: Some("/mnt/c:/Users".to_string()) })),
+            ("INPUT:Host: name", Some(Placeholder::Input { label: "Host: name".to_string() })),
+            ("INPUT:", None),
+            ("SECRET:Password", Some(Placeholder::Secret { label: "Password".to_string() })),
+            ("SECRET:", None),
+            ("CHOICE:a|b", Some(Placeholder::Choice { choices: vec!["a".to_string(), "b".to_string()] })),
+            ("ENV:HOME", Some(Placeholder::Env { name: "HOME".to_string() })),
+            ("CLIPBOARD", Some(Placeholder::Clipboard)),
+            ("CLIPBOARD:x", None),
+            ("input:Host", None),
+            ("file", None),
+        ];
+
+        for (content, expected) in cases {
+            assert_eq!(Placeholder::parse(content), *expected, "{content:?}");
+        }
+    }
+
+    #[test]
+    fn replaces_placeholders() {
+        let answers: &[(&str, &[&str])] = &[
+            ("<INPUT:Host>", &["example.org"]),
+            ("<FILES>", &["a.txt", "b.txt"]),
+            ("<FOLDER_PATH:/mnt/c:/Users>", &["/mnt/c:/Users/me"]),
+        ];
+
+        let cases: &[(&str, &[&str])] = &[
+            ("<FILES>", &["a.txt", "b.txt"]),
+            ("--files=<FILES>", &["--files=a.txt b.txt"]),
+            ("<FILES> <FILES>", &["a.txt b.txt a.txt b.txt"]),
+            ("cd <FOLDER_PATH:/mnt/c:/Users>", &["cd /mnt/c:/Users/me"]),
+            ("ssh <INPUT:Host", &["ssh <INPUT:Host"]),
+            ("<INPUT:Host>:22 <INPUT:Host", &["example.org:22 <INPUT:Host"]),
+            ("a < b > c", &["a < b > c"]),
+            ("<html>", &["<html>"]),
+        ];
+
+        for (text, expected) in cases {
+            assert_eq!(replace(text, false, answers), *expected, "{text:?}");
+        }
+    }
+
+    #[test]
+    fn finds_start_dirs() {
+        let folder = env::temp_dir().join(format!("desktop-tui-test-start-dir-{}", process::id()));
+        let missing = folder.join("missing");
+        fs::create_dir_all(&folder).unwrap();
+
+        let cwd = env::current_dir().unwrap();
+        let folder_text = folder.to_string_lossy().to_string();
+        let missing_text = missing.to_string_lossy().to_string();
+
+        // (folder of the previous answer, folder of the placeholder, dialog_dir, last folder used, start folder)
+        type Case<'a> = (Option<&'a Path>, Option<&'a str>, Option<&'a str>, Option<&'a Path>, PathBuf);
+
+        let cases: &[Case] = &[
+            (Some(&folder), Some("/"), Some("/"), None, folder.clone()),
+            (Some(&missing), Some(&folder_text), Some("/"), None, folder.clone()),
+            (Some(&missing), Some(&missing_text), Some(&folder_text), None, folder.clone()),
+            (None, Some(&missing_text), None, Some(&folder), folder.clone()),
+            (None, None, Some("."), Some(&folder), cwd.clone()),
+            (None, None, Some("~"), Some(&folder), home_dir()),
+            (Some(&missing), Some(&missing_text), Some(&missing_text), Some(&missing), home_dir()),
+            (None, None, None, None, home_dir()),
+        ];
+
+        for (last_answer, dir, dialog_dir, last_folder, expected) in cases {
+            let dialogs = Dialogs {
+                memory: DialogMemory { last_folder: last_folder.map(Path::to_path_buf), ..DialogMemory::default() },
+                dialog_dir: *dialog_dir,
+            };
+
+            assert_eq!(dialogs.start_dir(*last_answer, *dir), *expected, "{last_answer:?} {dir:?} {dialog_dir:?} {last_folder:?}");
+        }
+
+        fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[test]
+    fn remembers_answers() {
+        assert!(is_remembered("<FILE_PATH:*.toml>"));
+        assert!(is_remembered("<FOLDER_PATH>"));
+        assert!(is_remembered("<CHOICE:a|b>"));
+        assert!(is_remembered("<INPUT:Host>"));
+        assert!(!is_remembered("<SECRET:Password>"));
+        assert!(!is_remembered("<ENV:HOME>"));
+        assert!(!is_remembered("<CLIPBOARD>"));
+        assert!(!is_remembered("<html>"));
+    }
+}
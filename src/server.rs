@@ -0,0 +1,471 @@
+use anyhow::anyhow;
+use crossterm::terminal;
+use std::env;
+use std::fs;
+use std::io::{Read, Write};
+use std::net::Shutdown;
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::os::unix::process::CommandExt;
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use virtual_terminal::{Command, Input, Output};
+
+/// Set in the environment of a desktop run by a server, to the name of the server
+pub const SERVER_ENV: &str = "DESKTOP_TUI_SERVER";
+
+/// Time given to a new server to create its socket
+const SERVER_START_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Client messages, the server only sends the output of the desktop
+const DATA_MESSAGE: u8 = b'D';
+const RESIZE_MESSAGE: u8 = b'R';
+const DETACH_MESSAGE: u8 = b'X';
+
+/// Largest input accepted in one data message, the client sends at most one read of its terminal
+const MAX_DATA_LENGTH: usize = 64 * 1024;
+
+/// Time a client that stopped reading, for example suspended with Ctrl+Z, gets before it is disconnected
+const CLIENT_WRITE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// The client showing the desktop, with the number of its connection
+type AttachedClient = Arc<Mutex<Option<(usize, UnixStream)>>>;
+
+#[derive(Debug, PartialEq, Eq)]
+enum ClientMessage {
+    Data(Vec<u8>),
+    /// Columns and rows of the client terminal
+    Resize(u16, u16),
+    Detach,
+}
+
+/// Read the next message, `None` when the connection is closed or sends an invalid message
+fn read_message(stream: &mut impl Read) -> Option<ClientMessage> {
+    let mut message_type = [0u8; 1];
+    stream.read_exact(&mut message_type).ok()?;
+
+    match message_type[0] {
+        DATA_MESSAGE => {
+            let mut length = [0u8; 4];
+            stream.read_exact(&mut length).ok()?;
+
+            let length = u32::from_be_bytes(length) as usize;
+            if length > MAX_DATA_LENGTH {
+                return None;
+            }
+
+            let mut data = vec![0u8; length];
+            stream.read_exact(&mut data).ok()?;
+
+            Some(ClientMessage::Data(data))
+        }
+        RESIZE_MESSAGE => {
+            let mut size = [0u8; 4];
+            stream.read_exact(&mut size).ok()?;
+
+            Some(ClientMessage::Resize(u16::from_be_bytes([size[0], size[1]]), u16::from_be_bytes([size[2], size[3]])))
+        }
+        DETACH_MESSAGE => Some(ClientMessage::Detach),
+        _ => None,
+    }
+}
+
+fn data_message(data: &[u8]) -> Vec<u8> {
+    let mut message = vec![DATA_MESSAGE];
+    message.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    message.extend_from_slice(data);
+    message
+}
+
+fn resize_message(columns: u16, rows: u16) -> Vec<u8> {
+    let mut message = vec![RESIZE_MESSAGE];
+    message.extend_from_slice(&columns.to_be_bytes());
+    message.extend_from_slice(&rows.to_be_bytes());
+    message
+}
+
+/// Alternate screen and mouse tracking enabled by the desktop when it started, set again for each new client
+const ATTACH_SEQUENCE: &[u8] = b"\x1b[?1049h\x1b[?1000h\x1b[?1002h\x1b[?1003h\x1b[?1015h\x1b[?1006h";
+const DETACH_SEQUENCE: &[u8] = b"\x1b[?1006l\x1b[?1015l\x1b[?1003l\x1b[?1002l\x1b[?1000l\x1b[?25h\x1b[?1049l";
+
+/// `$XDG_RUNTIME_DIR/desktop-tui`, or `/tmp/desktop-tui-<uid>`
+pub fn socket_dir() -> PathBuf {
+    match env::var_os("XDG_RUNTIME_DIR") {
+        Some(runtime_dir) if !runtime_dir.is_empty() => PathBuf::from(runtime_dir).join("desktop-tui"),
+        _ => env::temp_dir().join(format!("desktop-tui-{}", unsafe { libc::getuid() })),
+    }
+}
+
+fn socket_path(name: &str) -> PathBuf {
+    socket_dir().join(format!("{name}.sock"))
+}
+
+/// Run the desktop with the given arguments in a pseudo-terminal, until it exits. One client at a time is attached to it
+pub async fn run_server(name: &str, desktop_args: Vec<String>) -> anyhow::Result<()> {
+    let socket_dir = socket_dir();
+    fs::create_dir_all(&socket_dir)?;
+    fs::set_permissions(&socket_dir, fs::Permissions::from_mode(0o700))?;
+
+    let socket_path = socket_path(name);
+
+    if UnixStream::connect(&socket_path).is_ok() {
+        return Err(anyhow!("A desktop named \"{name}\" is already running"));
+    }
+
+    // Left by a server that did not exit cleanly
+    fs::remove_file(&socket_path).ok();
+    let listener = UnixListener::bind(&socket_path)?;
+
+    let cmd = Command::new(env::current_exe()?)
+        .args(desktop_args)
+        .env(SERVER_ENV, name);
+
+    let tx = cmd.in_tx();
+    let rx = cmd.out_rx();
+    let client: AttachedClient = Arc::new(Mutex::new(None));
+
+    tokio::spawn(cmd.run());
+
+    let accept_client = client.clone();
+    thread::spawn(move || {
+        for (id, stream) in listener.incoming().flatten().enumerate() {
+            let (client, tx) = (accept_client.clone(), tx.clone());
+            thread::spawn(move || handle_client(stream, id, client, tx));
+        }
+    });
+
+    while let Ok(output) = rx.recv().await {
+        match output {
+            Output::Pid(_) => {}
+            Output::Stdout(data) => {
+                let client = client.clone();
+                tokio::task::spawn_blocking(move || send_output(&client, &data)).await.ok();
+            }
+            Output::Error(_) | Output::Terminated(_) => break,
+        }
+    }
+
+    if let Some((_, stream)) = client.lock().unwrap().take() {
+        stream.shutdown(Shutdown::Both).ok();
+    }
+
+    fs::remove_file(&socket_path).ok();
+
+    Ok(())
+}
+
+/// Write the output of the desktop to the attached client, output is dropped while detached,
+/// the desktop redraws itself when a client attaches.
+/// The lock is not held while writing, a stalled client must not block the next attach
+fn send_output(client: &AttachedClient, data: &[u8]) {
+    let attached = client
+        .lock()
+        .unwrap()
+        .as_ref()
+        .and_then(|(id, stream)| stream.try_clone().ok().map(|stream| (*id, stream)));
+
+    if let Some((id, mut stream)) = attached
+        && stream.write_all(data).is_err() {
+        disconnect_client(client, Some(id));
+    }
+}
+
+/// Replace the attached client, the previous one is disconnected
+fn attach_client(client: &AttachedClient, id: usize, stream: UnixStream) {
+    stream.set_write_timeout(Some(CLIENT_WRITE_TIMEOUT)).ok();
+
+    if let Some((_, previous_client)) = client.lock().unwrap().replace((id, stream)) {
+        previous_client.shutdown(Shutdown::Both).ok();
+    }
+}
+
+/// Disconnect the attached client, only when it is the given connection if there is one
+fn disconnect_client(client: &AttachedClient, id: Option<usize>) {
+    let mut client = client.lock().unwrap();
+
+    if let Some((attached_id, stream)) = client.as_ref()
+        && id.is_none_or(|id| id == *attached_id) {
+        stream.shutdown(Shutdown::Both).ok();
+        *client = None;
+    }
+}
+
+/// Forward the messages of a connection, a detach message disconnects the attached client
+fn handle_client(mut stream: UnixStream, id: usize, client: AttachedClient, tx: async_channel::Sender<Input>) {
+    let mut attached = false;
+
+    while let Some(message) = read_message(&mut stream) {
+        match message {
+            ClientMessage::Data(data) => {
+                tx.send_blocking(Input::Data(data)).ok();
+            }
+            ClientMessage::Resize(columns, rows) => {
+                let (columns, rows) = (columns as usize, rows as usize);
+
+                if !attached {
+                    attached = true;
+
+                    // A new client replaces the attached one
+                    if let Ok(new_client) = stream.try_clone() {
+                        attach_client(&client, id, new_client);
+                    }
+
+                    // Changing the size forces the desktop to redraw everything
+                    tx.send_blocking(Input::Resize((columns, rows.saturating_sub(1).max(1)))).ok();
+                }
+
+                tx.send_blocking(Input::Resize((columns, rows))).ok();
+            }
+            ClientMessage::Detach => {
+                disconnect_client(&client, None);
+                break;
+            }
+        }
+    }
+
+    stream.shutdown(Shutdown::Both).ok();
+}
+
+/// Raw mode and the screen of the desktop in the client terminal, restored when dropped, even after an error
+struct RawMode;
+
+impl RawMode {
+    fn enable() -> anyhow::Result<Self> {
+        terminal::enable_raw_mode()?;
+        let raw_mode = RawMode;
+
+        let mut stdout = std::io::stdout();
+        stdout.write_all(ATTACH_SEQUENCE)?;
+        stdout.flush()?;
+
+        Ok(raw_mode)
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        let mut stdout = std::io::stdout();
+        stdout.write_all(DETACH_SEQUENCE).ok();
+        stdout.flush().ok();
+        terminal::disable_raw_mode().ok();
+    }
+}
+
+/// Start a server in the background, detached from the current terminal
+fn start_server(name: &str, desktop_args: Vec<String>) -> anyhow::Result<()> {
+    let mut server = std::process::Command::new(env::current_exe()?);
+
+    server
+        .arg("server")
+        .arg("--name")
+        .arg(name)
+        .arg("--")
+        .args(desktop_args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+
+    // Closing the terminal must not send SIGHUP to the server
+    unsafe {
+        server.pre_exec(|| {
+            libc::setsid();
+            Ok(())
+        });
+    }
+
+    server.spawn()?;
+
+    Ok(())
+}
+
+/// Show the desktop with the given name in the current terminal, starting it if it is not running.
+/// Every key is sent to the desktop, which detaches the client with its Detach action
+pub fn attach(name: &str, desktop_args: Vec<String>) -> anyhow::Result<()> {
+    let socket_path = socket_path(name);
+
+    let stream = match UnixStream::connect(&socket_path) {
+        Ok(stream) => stream,
+        Err(_) => {
+            start_server(name, desktop_args)?;
+
+            let start = Instant::now();
+            loop {
+                match UnixStream::connect(&socket_path) {
+                    Ok(stream) => break stream,
+                    Err(error) if start.elapsed() >= SERVER_START_TIMEOUT => return Err(anyhow!("Failed to start the desktop \"{name}\": {error}")),
+                    Err(_) => thread::sleep(Duration::from_millis(50)),
+                }
+            }
+        }
+    };
+
+    let raw_mode = RawMode::enable()?;
+    let mut stdout = std::io::stdout();
+
+    let mut input_stream = stream.try_clone()?;
+    thread::spawn(move || {
+        let mut stdin = std::io::stdin();
+        let mut buffer = [0u8; 4096];
+
+        while let Ok(length) = stdin.read(&mut buffer) {
+            if length == 0 {
+                break;
+            }
+
+            if input_stream.write_all(&data_message(&buffer[..length])).is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut resize_stream = stream.try_clone()?;
+    thread::spawn(move || {
+        let mut last_size = None;
+
+        while let Ok(size) = terminal::size() {
+            if last_size != Some(size) {
+                if resize_stream.write_all(&resize_message(size.0, size.1)).is_err() {
+                    break;
+                }
+
+                last_size = Some(size);
+            }
+
+            thread::sleep(Duration::from_millis(200));
+        }
+    });
+
+    let mut output_stream = stream;
+    let mut buffer = [0u8; 4096];
+
+    while let Ok(length) = output_stream.read(&mut buffer) {
+        if length == 0 || stdout.write_all(&buffer[..length]).and_then(|_| stdout.flush()).is_err() {
+            break;
+        }
+    }
+
+    drop(raw_mode);
+
+    match UnixStream::connect(&socket_path) {
+        Ok(_) => println!("[detached from {name}]"),
+        Err(_) => println!("[{name} exited]"),
+    }
+
+    Ok(())
+}
+
+/// Disconnect the client attached to the desktop, which keeps running
+pub fn detach(name: &str) -> anyhow::Result<()> {
+    let mut stream = UnixStream::connect(socket_path(name))
+        .map_err(|_| anyhow!("No desktop named \"{name}\" is running"))?;
+
+    stream.write_all(&[DETACH_MESSAGE])?;
+
+    Ok(())
+}
+
+/// Names of the running desktops, the sockets of the ones that did not exit cleanly are removed
+pub fn list() -> anyhow::Result<Vec<String>> {
+    let Ok(entries) = fs::read_dir(socket_dir()) else {
+        return Ok(Vec::new());
+    };
+
+    let mut names = Vec::new();
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+
+        let Some(name) = path.file_stem().filter(|_| path.extension().is_some_and(|extension| extension == "sock")) else {
+            continue;
+        };
+
+        match UnixStream::connect(&path) {
+            Ok(_) => names.push(name.to_string_lossy().to_string()),
+            Err(_) => {
+                fs::remove_file(&path).ok();
+            }
+        }
+    }
+
+    names.sort();
+
+    Ok(names)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn reads_messages() {
+        let mut stream = data_message(b"ls\r");
+        stream.extend(resize_message(120, 40));
+        stream.extend(data_message(b""));
+        stream.push(DETACH_MESSAGE);
+        let mut stream = Cursor::new(stream);
+
+        assert_eq!(read_message(&mut stream), Some(ClientMessage::Data(b"ls\r".to_vec())));
+        assert_eq!(read_message(&mut stream), Some(ClientMessage::Resize(120, 40)));
+        assert_eq!(read_message(&mut stream), Some(ClientMessage::Data(Vec::new())));
+        assert_eq!(read_message(&mut stream), Some(ClientMessage::Detach));
+        assert_eq!(read_message(&mut stream), None);
+    }
+
+    #[test]
+    fn rejects_invalid_messages() {
+        let oversized = [&[DATA_MESSAGE][..], &u32::MAX.to_be_bytes()].concat();
+        let truncated_data = [&[DATA_MESSAGE][..], &4u32.to_be_bytes(), b"ab"].concat();
+        let truncated_length = vec![DATA_MESSAGE, 0, 0];
+        let truncated_resize = vec![RESIZE_MESSAGE, 0, 80, 0];
+        let unknown = vec![b'?'];
+
+        for stream in [oversized, truncated_data, truncated_length, truncated_resize, unknown] {
+            assert_eq!(read_message(&mut Cursor::new(&stream)), None, "{stream:?}");
+        }
+
+        let largest = data_message(&vec![b'x'; MAX_DATA_LENGTH]);
+        assert!(matches!(read_message(&mut Cursor::new(largest)), Some(ClientMessage::Data(data)) if data.len() == MAX_DATA_LENGTH));
+    }
+
+    #[test]
+    fn disconnects_stalled_clients() {
+        let client: AttachedClient = Arc::new(Mutex::new(None));
+        let (stream, _stalled_peer) = UnixStream::pair().unwrap();
+        attach_client(&client, 0, stream);
+
+        let start = Instant::now();
+        send_output(&client, &vec![b'x'; 16 * 1024 * 1024]);
+
+        assert!(client.lock().unwrap().is_none());
+        assert!(start.elapsed() < CLIENT_WRITE_TIMEOUT * 3);
+    }
+
+    #[test]
+    fn attaches_while_writing() {
+        let client: AttachedClient = Arc::new(Mutex::new(None));
+        let (stream, _stalled_peer) = UnixStream::pair().unwrap();
+        attach_client(&client, 0, stream);
+
+        let writer_client = client.clone();
+        let writer = thread::spawn(move || send_output(&writer_client, &vec![b'x'; 16 * 1024 * 1024]));
+        thread::sleep(Duration::from_millis(100));
+
+        // The stalled write does not hold the lock, and its failure does not disconnect the new client
+        let start = Instant::now();
+        let (stream, _peer) = UnixStream::pair().unwrap();
+        attach_client(&client, 1, stream);
+        assert!(start.elapsed() < CLIENT_WRITE_TIMEOUT);
+
+        writer.join().unwrap();
+        assert_eq!(client.lock().unwrap().as_ref().map(|(id, _)| *id), Some(1));
+
+        disconnect_client(&client, Some(0));
+        assert!(client.lock().unwrap().is_some());
+        disconnect_client(&client, None);
+        assert!(client.lock().unwrap().is_none());
+    }
+}
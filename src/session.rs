@@ -0,0 +1,188 @@
+use crate::panes::PaneTree;
+use crate::shortcut::LaunchOptions;
+use crate::tiling::Tiling;
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+/// Open windows and tiling state, saved on exit to be restored with `--restore`
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Session {
+    #[serde(default)]
+    pub current_workspace: usize,
+    /// Tiling of each workspace
+    #[serde(default)]
+    pub workspaces: Vec<Tiling>,
+    /// Windows in tiling order
+    #[serde(default)]
+    pub windows: Vec<WindowState>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct WindowState {
+    /// Name of the shortcut the window belongs to
+    pub shortcut: String,
+    /// Program and arguments of the first pane, as written in the shortcut so the placeholders are asked again
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    #[serde(default)]
+    pub workspace: usize,
+    #[serde(default)]
+    pub minimized: bool,
+    #[serde(default)]
+    pub floating: bool,
+    /// Working directory and environment the program was started with
+    #[serde(default)]
+    pub launch: LaunchOptions,
+    #[serde(default)]
+    pub active_tab: usize,
+    /// Tabs and pane splits, the first pane of the first tab runs the command above
+    #[serde(default)]
+    pub tabs: Vec<TabState>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TabState {
+    /// Label used until the application sets a title
+    pub name: String,
+    pub pane_tree: PaneTree,
+    pub active_pane: usize,
+    /// Panes in layout order
+    pub panes: Vec<PaneState>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PaneState {
+    /// Id of the pane in the tree of its tab
+    pub id: usize,
+    /// Program and arguments as written in the shortcut
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default)]
+    pub launch: LaunchOptions,
+}
+
+/// `$XDG_STATE_HOME/desktop-tui`, or `~/.local/state/desktop-tui`
+pub fn state_dir() -> Option<PathBuf> {
+    let state_home = match env::var_os("XDG_STATE_HOME") {
+        Some(state_home) if !state_home.is_empty() => PathBuf::from(state_home),
+        _ => PathBuf::from(env::var_os("HOME")?).join(".local").join("state"),
+    };
+
+    Some(state_home.join("desktop-tui"))
+}
+
+/// `$XDG_STATE_HOME/desktop-tui/session.toml`, or `~/.local/state/desktop-tui/session.toml`
+pub fn default_session_path() -> Option<PathBuf> {
+    Some(state_dir()?.join("session.toml"))
+}
+
+/// Parse the saved session, there is none if it was never saved
+pub fn load_session() -> anyhow::Result<Option<Session>> {
+    let Some(session_path) = default_session_path().filter(|session_path| session_path.is_file()) else {
+        return Ok(None);
+    };
+
+    let file_content = fs::read_to_string(&session_path)?;
+    let session = toml::from_str::<Session>(&file_content)?;
+
+    Ok(Some(session))
+}
+
+pub fn save_session(session: &Session) -> anyhow::Result<()> {
+    let Some(session_path) = default_session_path() else {
+        return Ok(());
+    };
+
+    if let Some(parent) = session_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    fs::write(session_path, toml::to_string(session)?)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::panes::SplitDirection;
+
+    #[test]
+    fn saves_tabs() {
+        let mut pane_tree = PaneTree::Pane(0);
+        pane_tree.split(0, 3, SplitDirection::Down, 0.3);
+
+        let pane = |id: usize, command: &str| PaneState {
+            id,
+            command: command.to_string(),
+            args: vec!["<FILE>".to_string()],
+            launch: LaunchOptions::default(),
+        };
+
+        let session = Session {
+            windows: vec![WindowState {
+                shortcut: "Editor".to_string(),
+                command: "vim".to_string(),
+                args: vec!["<FILE>".to_string()],
+                x: 1,
+                y: 2,
+                width: 80,
+                height: 24,
+                workspace: 0,
+                minimized: false,
+                floating: false,
+                launch: LaunchOptions::default(),
+                active_tab: 1,
+                tabs: vec![
+                    TabState {
+                        name: "Editor #1".to_string(),
+                        pane_tree: pane_tree.clone(),
+                        active_pane: 3,
+                        panes: vec![pane(0, "vim"), pane(3, "less")],
+                    },
+                    TabState {
+                        name: "Editor #2".to_string(),
+                        pane_tree: PaneTree::Pane(4),
+                        active_pane: 4,
+                        panes: vec![pane(4, "vim")],
+                    },
+                ],
+            }],
+            ..Session::default()
+        };
+
+        let saved: Session = toml::from_str(&toml::to_string(&session).unwrap()).unwrap();
+        let window = &saved.windows[0];
+
+        assert_eq!(window.active_tab, 1);
+        assert_eq!(window.tabs.len(), 2);
+        assert_eq!(window.tabs[0].pane_tree, pane_tree);
+        assert_eq!(window.tabs[0].panes[1].command, "less");
+        assert_eq!(window.tabs[0].panes[1].args, ["<FILE>"]);
+        assert_eq!(window.tabs[1].pane_tree, PaneTree::Pane(4));
+    }
+
+    #[test]
+    fn loads_sessions_without_tabs() {
+        let session: Session = toml::from_str(r#"
+            [[windows]]
+            shortcut = "Terminal"
+            command = "bash"
+            x = 0
+            y = 0
+            width = 80
+            height = 24
+        "#).unwrap();
+
+        assert!(session.windows[0].tabs.is_empty());
+        assert_eq!(session.windows[0].active_tab, 0);
+    }
+}
@@ -1,42 +1,116 @@
+use crate::panes::SplitDirection;
+use crate::utils::{closest_match, expand_vars, find_program};
 use nestify::nest;
+use anyhow::anyhow;
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use toml::{Table, Value};
 use std::{env, fs};
 use walkdir::WalkDir;
 
 nest! {
     #[derive(Clone, Debug, Serialize, Deserialize)]
+    #[serde(deny_unknown_fields)]
     pub struct Shortcut {
         pub name: String,
 
+        /// Replace the shortcut of the same name read before it, the first one is kept otherwise
+        #[serde(default, rename = "override", skip_serializing_if = "std::ops::Not::not")]
+        pub overrides: bool,
+
+        /// File the shortcut was parsed from
+        #[serde(skip)]
+        pub path: PathBuf,
+
+        /// Problems found while loading the file, the shortcut is shown disabled when there are some
+        #[serde(skip)]
+        pub errors: Vec<String>,
+
         pub command: String,
 
         #[serde(default)]
         pub args: Vec<String>,
 
-        pub taskbar:
+        /// Directory the command is started in, `~` and `$VAR` are expanded
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub cwd: Option<String>,
+
+        /// Variables added to the environment of the command, `~` and `$VAR` are expanded in the values
+        #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+        pub env: BTreeMap<String, String>,
+
+        /// Start the command with only the variables of `env`
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        pub env_clear: bool,
+
+        /// Run the command and its arguments as a line of `$SHELL -c`, for pipes and globbing
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        pub shell: bool,
+
+        /// Folder the file and folder dialogs of the placeholders start in, when the placeholder does not give one.
+        /// Defaults to the last folder used, then the home directory
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub dialog_dir: Option<String>,
+
+        /// Panes opened next to the first one, each one splitting the previous pane
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        pub panes: Vec<
             #[derive(Clone, Debug, Serialize, Deserialize)]
+            #[serde(deny_unknown_fields)]
+            pub struct PaneOptions {
+                pub split: SplitDirection,
+                pub command: String,
+                #[serde(default)]
+                pub args: Vec<String>,
+                /// Part of the space kept by the previous pane, half by default
+                pub ratio: Option<f32>,
+            }
+        >,
+
+        #[serde(default)]
+        pub taskbar:
+            #[derive(Clone, Debug, Default, Serialize, Deserialize)]
+            #[serde(deny_unknown_fields)]
             pub struct TaskbarOptions {
                 pub position: Option<u32>,
                 #[serde(default)]
                 pub additional_commands: Vec<
                     #[derive(Clone, Debug, Serialize, Deserialize)]
+                    #[serde(deny_unknown_fields)]
                     pub struct TaskbarCommand {
                         pub name: String,
                         pub command: String,
+                        #[serde(default)]
                         pub args: Vec<String>,
+                        #[serde(default, skip_serializing_if = "Option::is_none")]
+                        pub cwd: Option<String>,
+                        #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+                        pub env: BTreeMap<String, String>,
+                        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+                        pub env_clear: bool,
+                        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+                        pub shell: bool,
                     }
                 >
             },
 
+        #[serde(default)]
         pub window:
             #[derive(Clone, Debug, Serialize, Deserialize)]
+            #[serde(deny_unknown_fields)]
             pub struct WindowOptions {
+                #[serde(default = "default_true")]
                 pub resizable: bool,
+                #[serde(default = "default_true")]
                 pub close_button: bool,
+                #[serde(default)]
                 pub fixed_position: bool,
+                /// Workspace the windows are opened on, starting at 1. Defaults to the current one
+                pub workspace: Option<usize>,
                 pub size: Option<
                     #[derive(Clone, Debug, Serialize, Deserialize)]
+                    #[serde(deny_unknown_fields)]
                     pub struct WindowSize {
                         pub width: u32,
                         pub height: u32,
@@ -44,13 +118,16 @@ nest! {
                 >
             },
 
+        #[serde(default)]
         pub terminal:
-            #[derive(Clone, Debug, Serialize, Deserialize)]
+            #[derive(Clone, Debug, Default, Serialize, Deserialize)]
+            #[serde(deny_unknown_fields)]
             pub struct TerminalOptions {
                 pub padding: Option<(i32, i32)>,
                 // AppCUI-rs needs to add serde
                 pub background_color: Option<
                     #[derive(Clone, Debug, Serialize, Deserialize)]
+                    #[serde(deny_unknown_fields)]
                     pub struct BackgroundColor {
                         pub r: u8,
                         pub g: u8,
@@ -61,34 +138,480 @@ nest! {
     }
 }
 
-pub fn parse_shortcut_dir(shortcut_path: PathBuf) -> anyhow::Result<Vec<Shortcut>> {
-    let mut desktop_entries = Vec::<Shortcut>::new();
+impl Default for WindowOptions {
+    fn default() -> Self {
+        Self {
+            resizable: true,
+            close_button: true,
+            fixed_position: false,
+            workspace: None,
+            size: None,
+        }
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// Where and how a command is started, from the fields of the same name of a shortcut or an additional command
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct LaunchOptions {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub env_clear: bool,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub shell: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dialog_dir: Option<String>,
+}
+
+/// TOML files of the directory and its subdirectories
+pub fn shortcut_files(shortcut_dir: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
 
-    for entry in WalkDir::new(env::current_dir()?.join(shortcut_path)).into_iter().filter_map(|e| e.ok()) {
+    for entry in WalkDir::new(env::current_dir()?.join(shortcut_dir)).sort_by_file_name().into_iter().filter_map(|e| e.ok()) {
         let entry_path = entry.path();
-        if entry_path.is_dir() || entry_path.extension().is_none() || !entry_path.extension().unwrap().to_str().unwrap().ends_with("toml") {
+        if entry_path.is_dir() || !entry_path.extension().is_some_and(|extension| extension.to_string_lossy().ends_with("toml")) {
             continue;
         }
 
-        let file_content = fs::read_to_string(entry.path())?;
-        let desktop_entry = toml::from_str::<Shortcut>(&file_content)?;
+        files.push(entry_path.to_path_buf());
+    }
+
+    Ok(files)
+}
 
-        let exists = desktop_entries.iter().find(|entry| entry.name == desktop_entry.name);
+/// Parse the file, errors start with their line and column
+pub fn parse_shortcut_file(shortcut_path: &Path) -> anyhow::Result<Shortcut> {
+    let file_content = fs::read_to_string(shortcut_path)?;
 
-        if exists.is_some() {
-            continue;
+    toml::from_str::<Shortcut>(&file_content).map_err(|error| {
+        let message = with_suggestion(error.message());
+
+        match error.span() {
+            Some(span) => {
+                let (line, column) = line_column(&file_content, span.start);
+                anyhow!("{line}:{column}: {message}")
+            }
+            None => anyhow!("{message}"),
+        }
+    })
+}
+
+/// Line and column of a byte offset, both counted from 1, columns in characters
+fn line_column(content: &str, offset: usize) -> (usize, usize) {
+    let before = &content[..offset];
+    let line = before.matches('\n').count() + 1;
+    let column = before.chars().rev().take_while(|character| *character != '\n').count() + 1;
+
+    (line, column)
+}
+
+/// Error message followed by the field the user most likely meant, if a field is unknown
+fn with_suggestion(message: &str) -> String {
+    let message = message.trim_end();
+
+    match unknown_field_suggestion(message) {
+        Some(field) => format!("{message}, did you mean `{field}`?"),
+        None => message.to_string(),
+    }
+}
+
+/// Expected field closest to the unknown one, from a message such as "unknown field `commands`, expected `position` or `additional_commands`"
+fn unknown_field_suggestion(message: &str) -> Option<String> {
+    let (_, rest) = message.split_once("unknown field `")?;
+    let (field, expected) = rest.split_once('`')?;
+
+    let candidates: Vec<&str> = expected.split('`').skip(1).step_by(2).collect();
+
+    closest_match(field, &candidates).map(str::to_string)
+}
+
+/// Parse the file with the `[defaults]` of the config under it, and check that its command can be started.
+/// A file that cannot be parsed gives a disabled shortcut named after its `name` key or its file name
+pub fn load_shortcut_file(shortcut_path: &Path, defaults: &Table) -> Shortcut {
+    let location = shortcut_path.display();
+
+    // Parsed alone first, so its errors point to its lines
+    let parsed = parse_shortcut_file(shortcut_path).and_then(|shortcut| match defaults.is_empty() {
+        true => Ok(shortcut),
+        false => {
+            let mut table = defaults.clone();
+            merge_tables(&mut table, fs::read_to_string(shortcut_path)?.parse::<Table>()?);
+            Ok(Value::Table(table).try_into::<Shortcut>().map_err(|error| anyhow!("with [defaults]: {}", with_suggestion(error.message())))?)
         }
+    });
+
+    let mut shortcut = match parsed {
+        Ok(shortcut) => shortcut,
+        Err(error) => {
+            let name = fs::read_to_string(shortcut_path)
+                .ok()
+                .and_then(|file_content| file_content.parse::<toml::Table>().ok())
+                .and_then(|table| table.get("name")?.as_str().map(str::to_string))
+                .or_else(|| shortcut_path.file_stem().map(|file_stem| file_stem.to_string_lossy().to_string()))
+                .unwrap_or_default();
+
+            Shortcut::broken(name, format!("{location}:{error}"))
+        }
+    };
+
+    // The shell finds the commands of its line itself
+    if shortcut.errors.is_empty() && !shortcut.shell && find_program(&shortcut.command).is_none() {
+        shortcut.errors.push(format!("{location}: command \"{}\" was not found in $PATH", shortcut.command));
+    }
 
-        desktop_entries.push(desktop_entry);
+    if let Some(cwd) = &shortcut.cwd
+        && shortcut.errors.is_empty()
+        && !Path::new(&expand_vars(cwd)).is_dir() {
+        shortcut.errors.push(format!("{location}: working directory \"{cwd}\" does not exist"));
     }
 
-    desktop_entries
-        .sort_by(
-            |a, b|
-                a.taskbar.position
-                .unwrap_or(99)
-                .cmp(&b.taskbar.position.unwrap_or(99))
-        );
+    shortcut.path = shortcut_path.to_path_buf();
+    shortcut
+}
+
+/// Check the `[defaults]` of the config by applying them to a shortcut
+pub fn check_shortcut_defaults(defaults: &Table) -> anyhow::Result<()> {
+    let mut table = defaults.clone();
+    table.insert("name".to_string(), Value::String(String::new()));
+    table.insert("command".to_string(), Value::String(String::new()));
+
+    Value::Table(table)
+        .try_into::<Shortcut>()
+        .map_err(|error| anyhow!("[defaults]: {}", with_suggestion(error.message())))?;
+
+    Ok(())
+}
+
+/// Values of `overrides` replace the ones of `table`, nested tables are merged key by key
+fn merge_tables(table: &mut Table, overrides: Table) {
+    for (key, value) in overrides {
+        match (table.get_mut(&key), value) {
+            (Some(Value::Table(table)), Value::Table(overrides)) => merge_tables(table, overrides),
+            (_, value) => {
+                table.insert(key, value);
+            }
+        }
+    }
+}
+
+impl Shortcut {
+    /// Placeholder of a file that cannot be parsed
+    fn broken(name: String, error: String) -> Self {
+        Self {
+            name,
+            overrides: false,
+            path: PathBuf::new(),
+            errors: vec![error],
+            command: String::new(),
+            args: Vec::new(),
+            cwd: None,
+            env: BTreeMap::new(),
+            env_clear: false,
+            shell: false,
+            dialog_dir: None,
+            panes: Vec::new(),
+            taskbar: TaskbarOptions::default(),
+            window: WindowOptions::default(),
+            terminal: TerminalOptions::default(),
+        }
+    }
+
+    pub fn launch_options(&self) -> LaunchOptions {
+        LaunchOptions {
+            cwd: self.cwd.clone(),
+            env: self.env.clone(),
+            env_clear: self.env_clear,
+            shell: self.shell,
+            dialog_dir: self.dialog_dir.clone(),
+        }
+    }
+}
 
-    Ok(desktop_entries)
-}
\ No newline at end of file
+impl TaskbarCommand {
+    /// The dialogs start in the `dialog_dir` of the shortcut
+    pub fn launch_options(&self, shortcut: &Shortcut) -> LaunchOptions {
+        LaunchOptions {
+            cwd: self.cwd.clone(),
+            env: self.env.clone(),
+            env_clear: self.env_clear,
+            shell: self.shell,
+            dialog_dir: shortcut.dialog_dir.clone(),
+        }
+    }
+}
+
+/// A shortcut replaced by another one with the same name
+#[derive(Clone, Debug)]
+pub struct ShadowedShortcut {
+    pub name: String,
+    pub path: PathBuf,
+    /// File of the shortcut that is used
+    pub used_path: PathBuf,
+}
+
+/// System-wide directories from `$XDG_CONFIG_DIRS` (or `/etc/xdg`), then the user one from `$XDG_CONFIG_HOME` (or `~/.config`),
+/// then the given ones. The current directory stands for the default ones when none of them exist and none is given
+pub fn shortcut_dirs(given_dirs: Vec<PathBuf>) -> Vec<PathBuf> {
+    let system_dirs = match env::var("XDG_CONFIG_DIRS") {
+        Ok(config_dirs) if !config_dirs.is_empty() => config_dirs.split(':').rev().map(PathBuf::from).collect(),
+        _ => vec![PathBuf::from("/etc/xdg")],
+    };
+
+    let user_dir = match env::var_os("XDG_CONFIG_HOME") {
+        Some(config_home) if !config_home.is_empty() => Some(PathBuf::from(config_home)),
+        _ => env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")),
+    };
+
+    let default_dirs: Vec<PathBuf> = system_dirs
+        .into_iter()
+        .chain(user_dir)
+        .map(|config_dir| config_dir.join("desktop-tui").join("shortcuts"))
+        .filter(|shortcut_dir| shortcut_dir.is_dir())
+        .collect();
+
+    match default_dirs.is_empty() && given_dirs.is_empty() {
+        true => vec![PathBuf::from(".")],
+        false => default_dirs.into_iter().chain(given_dirs).collect(),
+    }
+}
+
+/// Shortcuts of the directories in appbar order, including the broken ones, and the ones that were shadowed.
+/// The first shortcut of a name is kept, a shortcut found after it replaces it only when it sets `override = true`.
+/// Missing directories are skipped, see `missing_dirs`
+pub fn parse_shortcut_dirs(shortcut_dirs: &[PathBuf], defaults: &Table) -> anyhow::Result<(Vec<Shortcut>, Vec<ShadowedShortcut>)> {
+    let mut desktop_entries = Vec::<Shortcut>::new();
+    let mut shadowed = Vec::new();
+
+    for shortcut_dir in shortcut_dirs.iter().filter(|shortcut_dir| shortcut_dir.is_dir()) {
+        for shortcut_path in shortcut_files(shortcut_dir)? {
+            let desktop_entry = load_shortcut_file(&shortcut_path, defaults);
+
+            let Some(existing) = desktop_entries.iter_mut().find(|entry| entry.name == desktop_entry.name) else {
+                desktop_entries.push(desktop_entry);
+                continue;
+            };
+
+            let (used, replaced) = match desktop_entry.overrides {
+                true => (desktop_entry, existing.clone()),
+                false => (existing.clone(), desktop_entry),
+            };
+
+            shadowed.push(ShadowedShortcut {
+                name: replaced.name,
+                path: replaced.path,
+                used_path: used.path.clone(),
+            });
+
+            *existing = used;
+        }
+    }
+
+    desktop_entries.sort_by_key(|entry| entry.taskbar.position.unwrap_or(99));
+
+    Ok((desktop_entries, shadowed))
+}
+
+/// One line per shortcut directory that does not exist
+pub fn missing_dirs(shortcut_dirs: &[PathBuf]) -> Vec<String> {
+    shortcut_dirs
+        .iter()
+        .filter(|shortcut_dir| !shortcut_dir.is_dir())
+        .map(|shortcut_dir| format!("{} is not a directory", shortcut_dir.display()))
+        .collect()
+}
+
+impl ShadowedShortcut {
+    /// One line of the startup report
+    pub fn describe(&self) -> String {
+        format!("\"{}\" of {} is replaced by {}", self.name, self.path.display(), self.used_path.display())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process;
+
+    /// A new directory with the given `(folder, file name, content)` files
+    fn test_dir(name: &str, files: &[(&str, &str, &str)]) -> PathBuf {
+        let dir = env::temp_dir().join(format!("desktop-tui-test-{name}-{}", process::id()));
+        fs::remove_dir_all(&dir).ok();
+
+        for (folder, file_name, content) in files {
+            fs::create_dir_all(dir.join(folder)).unwrap();
+            fs::write(dir.join(folder).join(file_name), content).unwrap();
+        }
+
+        dir
+    }
+
+    #[test]
+    fn merges_shortcut_dirs() {
+        let dir = test_dir("merge", &[
+            ("system", "a.toml", "name = \"A\"\ncommand = \"sh\"\ntaskbar.position = 2"),
+            ("system", "b.toml", "name = \"B\"\ncommand = \"sh\""),
+            ("system", "c.toml", "name = \"C\"\ncommand = \"sh\""),
+            ("user", "a.toml", "name = \"A\"\ncommand = \"ls\""),
+            ("user", "b.toml", "name = \"B\"\ncommand = \"ls\"\noverride = true"),
+            ("user", "c.toml", "name = \"C\"\ncommand = \"ls\"\noverride = true"),
+            ("given", "c.toml", "name = \"C\"\ncommand = \"cat\"\noverride = true"),
+            ("given", "d.toml", "name = \"D\"\ncommand = \"sh\"\ntaskbar.position = 1"),
+        ]);
+        let dirs = ["system", "user", "missing", "given"].map(|folder| dir.join(folder));
+
+        let (shortcuts, shadowed) = parse_shortcut_dirs(&dirs, &Table::new()).unwrap();
+
+        // Appbar order, then file order
+        let commands: Vec<(&str, &str)> = shortcuts.iter().map(|shortcut| (shortcut.name.as_str(), shortcut.command.as_str())).collect();
+        assert_eq!(commands, [("D", "sh"), ("A", "sh"), ("B", "ls"), ("C", "cat")]);
+
+        // (name, shadowed file, used file)
+        let shadowed: Vec<(&str, PathBuf, PathBuf)> = shadowed.iter().map(|shadowed| (shadowed.name.as_str(), shadowed.path.clone(), shadowed.used_path.clone())).collect();
+        assert_eq!(shadowed, [
+            ("A", dir.join("user/a.toml"), dir.join("system/a.toml")),
+            ("B", dir.join("system/b.toml"), dir.join("user/b.toml")),
+            ("C", dir.join("system/c.toml"), dir.join("user/c.toml")),
+            ("C", dir.join("user/c.toml"), dir.join("given/c.toml")),
+        ]);
+
+        assert_eq!(missing_dirs(&dirs), [format!("{} is not a directory", dir.join("missing").display())]);
+
+        fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn locates_errors() {
+        let content = "name = \"é\"\r\ncommand = \"sh\"\n\n  args = 1";
+
+        // (offset, line and column)
+        let cases = [
+            (0, (1, 1)),
+            (7, (1, 8)),
+            // After the two bytes of "é"
+            (10, (1, 10)),
+            (13, (2, 1)),
+            (content.find("args").unwrap(), (4, 3)),
+            (content.len(), (4, 11)),
+        ];
+
+        for (offset, expected) in cases {
+            assert_eq!(line_column(content, offset), expected, "offset {offset}");
+        }
+    }
+
+    #[test]
+    fn suggests_fields() {
+        let cases = [
+            (
+                "unknown field `comand`, expected `name` or `command`\n",
+                "unknown field `comand`, expected `name` or `command`, did you mean `command`?",
+            ),
+            (
+                "unknown field `commands`, expected `position` or `additional_commands`",
+                "unknown field `commands`, expected `position` or `additional_commands`, did you mean `additional_commands`?",
+            ),
+            (
+                "unknown field `zzz`, expected `name` or `command`",
+                "unknown field `zzz`, expected `name` or `command`",
+            ),
+            (
+                "invalid type: integer `1`, expected a sequence\n",
+                "invalid type: integer `1`, expected a sequence",
+            ),
+        ];
+
+        for (message, expected) in cases {
+            assert_eq!(with_suggestion(message), expected);
+        }
+    }
+
+    #[test]
+    fn reports_error_locations() {
+        let dir = test_dir("locations", &[
+            ("shortcuts", "typo.toml", "name = \"Typo\"\n  comand = \"sh\""),
+        ]);
+
+        let error = parse_shortcut_file(&dir.join("shortcuts/typo.toml")).unwrap_err().to_string();
+
+        assert!(error.starts_with("2:3: unknown field `comand`"), "{error}");
+        assert!(error.ends_with("did you mean `command`?"), "{error}");
+
+        fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn merges_tables() {
+        // (table, overrides, merged)
+        let cases = [
+            ("a = 1\nb = 2", "b = 3\nc = 4", "a = 1\nb = 3\nc = 4"),
+            ("", "a = 1", "a = 1"),
+            ("a = 1", "", "a = 1"),
+            ("[t]\na = 1\nb = 2", "[t]\nb = 3", "[t]\na = 1\nb = 3"),
+            ("[t.u]\na = 1\nb = 2", "[t.u]\na = 3", "[t.u]\na = 3\nb = 2"),
+            // A value that is not a table on one side is replaced as a whole
+            ("t = 1", "[t]\na = 1", "[t]\na = 1"),
+            ("[t]\na = 1", "t = 2", "t = 2"),
+            ("a = [1, 2]", "a = [3]", "a = [3]"),
+        ];
+
+        for (table, overrides, expected) in cases {
+            let mut merged = table.parse::<Table>().unwrap();
+            merge_tables(&mut merged, overrides.parse::<Table>().unwrap());
+            assert_eq!(merged, expected.parse::<Table>().unwrap(), "{table} + {overrides}");
+        }
+    }
+
+    #[test]
+    fn inherits_defaults() {
+        let dir = test_dir("defaults", &[
+            ("shortcuts", "plain.toml", "name = \"Plain\"\ncommand = \"sh\""),
+            ("shortcuts", "window.toml", "name = \"Window\"\ncommand = \"sh\"\nargs = [\"-l\"]\n[window]\nresizable = false\nworkspace = 3"),
+        ]);
+        let defaults = "args = [\"-i\"]\nshell = true\n[window]\nclose_button = false\nworkspace = 2\n[terminal]\npadding = [1, 2]".parse::<Table>().unwrap();
+
+        let plain = load_shortcut_file(&dir.join("shortcuts/plain.toml"), &defaults);
+        assert_eq!(plain.errors, Vec::<String>::new());
+        assert_eq!(plain.args, ["-i"]);
+        assert!(plain.shell);
+        assert!(plain.window.resizable);
+        assert!(!plain.window.close_button);
+        assert_eq!(plain.window.workspace, Some(2));
+        assert_eq!(plain.terminal.padding, Some((1, 2)));
+
+        // The values of the shortcut win, the other fields of its tables come from the defaults
+        let window = load_shortcut_file(&dir.join("shortcuts/window.toml"), &defaults);
+        assert_eq!(window.args, ["-l"]);
+        assert!(window.shell);
+        assert!(!window.window.resizable);
+        assert!(!window.window.close_button);
+        assert_eq!(window.window.workspace, Some(3));
+
+        // Without [defaults] the shortcut is the one of its file alone
+        let alone = load_shortcut_file(&dir.join("shortcuts/window.toml"), &Table::new());
+        let parsed = parse_shortcut_file(&dir.join("shortcuts/window.toml")).unwrap();
+        assert_eq!(alone.args, parsed.args);
+        assert!(!alone.shell);
+        assert!(!alone.window.resizable);
+        assert!(alone.window.close_button);
+        assert_eq!(alone.window.workspace, Some(3));
+        assert_eq!(alone.terminal.padding, None);
+
+        fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn checks_defaults() {
+        assert!(check_shortcut_defaults(&Table::new()).is_ok());
+        assert!(check_shortcut_defaults(&"[window]\nworkspace = 2".parse::<Table>().unwrap()).is_ok());
+
+        let error = check_shortcut_defaults(&"[window]\nworkspac = 2".parse::<Table>().unwrap()).unwrap_err().to_string();
+        assert!(error.starts_with("[defaults]: unknown field `workspac`"), "{error}");
+        assert!(error.ends_with("did you mean `workspace`?"), "{error}");
+    }
+}
@@ -0,0 +1,69 @@
+use appcui::prelude::*;
+
+/// Labels of the tabs of a window, a click selects a tab
+#[CustomControl(overwrite = OnPaint + OnMouseEvent)]
+pub struct TabBar {
+    pub labels: Vec<String>,
+    pub active: usize,
+    /// Tab clicked since the window last checked
+    pub clicked: Option<usize>,
+}
+
+impl TabBar {
+    pub fn new(layout: Layout) -> Self {
+        Self {
+            base: ControlBase::new(layout, false),
+            labels: Vec::new(),
+            active: 0,
+            clicked: None,
+        }
+    }
+
+    /// Start and end columns of each label
+    fn label_ranges(&self) -> Vec<(i32, i32)> {
+        let mut x = 0;
+
+        self.labels
+            .iter()
+            .map(|label| {
+                let start = x;
+                x += label.chars().count() as i32 + 2;
+                let range = (start, x - 1);
+                x += 1;
+                range
+            })
+            .collect()
+    }
+}
+
+impl OnPaint for TabBar {
+    fn on_paint(&self, surface: &mut Surface, theme: &Theme) {
+        surface.clear(Character::with_attributes(' ', theme.text.inactive));
+
+        for (index, (label, (start, end))) in self.labels.iter().zip(self.label_ranges()).enumerate() {
+            let attribute = match index == self.active {
+                true => theme.list_current_item.focus,
+                false => theme.text.normal,
+            };
+
+            surface.fill_horizontal_line(start, 0, end, Character::with_attributes(' ', attribute));
+            surface.write_string(start + 1, 0, label, attribute, false);
+        }
+    }
+}
+
+impl OnMouseEvent for TabBar {
+    fn on_mouse_event(&mut self, event: &MouseEvent) -> EventProcessStatus {
+        let MouseEvent::Pressed(data) = event else {
+            return EventProcessStatus::Ignored;
+        };
+
+        match self.label_ranges().iter().position(|(start, end)| (*start..=*end).contains(&data.x)) {
+            Some(index) => {
+                self.clicked = Some(index);
+                EventProcessStatus::Processed
+            }
+            None => EventProcessStatus::Ignored,
+        }
+    }
+}
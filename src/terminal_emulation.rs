@@ -1,4 +1,73 @@
 use appcui::prelude::{CharFlags, Character, Color, Surface};
+use std::collections::VecDeque;
+
+/// Maximum number of lines kept above the visible screen
+const SCROLLBACK_LIMIT: usize = 1000;
+
+/// Maximum number of kitty keyboard flags pushed by the application, the oldest ones are dropped
+const KEYBOARD_FLAGS_STACK_LIMIT: usize = 16;
+
+/// Primary device attributes, VT100 with advanced video option
+const DEVICE_ATTRIBUTES: &[u8] = b"\x1B[?1;2c";
+
+/// Character set designated to G0 or G1
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum Charset {
+    #[default]
+    Ascii,
+    /// DEC special graphics, the line drawing characters of `ESC ( 0`
+    DecSpecialGraphics,
+}
+
+impl Charset {
+    fn designated(final_character: char) -> Self {
+        match final_character {
+            '0' => Charset::DecSpecialGraphics,
+            _ => Charset::Ascii,
+        }
+    }
+
+    fn map(self, character: char) -> char {
+        if self == Charset::Ascii {
+            return character;
+        }
+
+        match character {
+            '`' => '◆',
+            'a' => '▒',
+            'b' => '␉',
+            'c' => '␌',
+            'd' => '␍',
+            'e' => '␊',
+            'f' => '°',
+            'g' => '±',
+            'h' => '␤',
+            'i' => '␋',
+            'j' => '┘',
+            'k' => '┐',
+            'l' => '┌',
+            'm' => '└',
+            'n' => '┼',
+            'o' => '⎺',
+            'p' => '⎻',
+            'q' => '─',
+            'r' => '⎼',
+            's' => '⎽',
+            't' => '├',
+            'u' => '┤',
+            'v' => '┴',
+            'w' => '┬',
+            'x' => '│',
+            'y' => '≤',
+            'z' => '≥',
+            '{' => 'π',
+            '|' => '≠',
+            '}' => '£',
+            '~' => '·',
+            character => character,
+        }
+    }
+}
 
 #[derive(Debug, Clone, Copy)]
 struct TerminalState {
@@ -10,11 +79,18 @@ struct TerminalState {
     dim: bool,
     italic: bool,
     underline: bool,
+    /// Foreground and background swapped
+    inverse: bool,
     cursor_x: i32,
     cursor_y: i32,
+    /// G0 and G1 character sets
+    charsets: [Charset; 2],
+    /// Shift out (SO) selected G1, shift in (SI) selects G0 again
+    shift_out: bool,
 }
 
 impl TerminalState {
+    /// Reset the attributes, the cursor stays in place
     fn reset(&mut self) {
         self.foreground = self.default_foreground_color;
         self.background = self.default_background_color;
@@ -22,8 +98,73 @@ impl TerminalState {
         self.dim = false;
         self.italic = false;
         self.underline = false;
-        self.cursor_x = 0;
-        self.cursor_y = 0;
+        self.inverse = false;
+    }
+
+    fn blank(&self) -> Character {
+        Character::new(' ', self.default_foreground_color, self.default_background_color, CharFlags::None)
+    }
+
+    fn erased(&self) -> Character {
+        Character::new(' ', self.foreground, self.background, CharFlags::None)
+    }
+}
+
+/// Keyboard modes requested by the application, used to encode the keys sent back to it
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct InputModes {
+    /// DECCKM, cursor keys send `ESC O` sequences instead of `ESC [` ones
+    pub application_cursor_keys: bool,
+    /// Kitty keyboard protocol progressive enhancement flags (`CSI > flags u`).
+    /// AppCUI does not report key releases, so event types are never sent
+    pub kitty_keyboard_flags: u8,
+    /// xterm modifyOtherKeys level (`CSI > 4 ; level m`)
+    pub modify_other_keys: u8,
+    /// Mouse events reported to the application
+    pub mouse_tracking: MouseTracking,
+    /// Mouse events are reported as `CSI < button ; x ; y M` (`CSI ? 1006 h`) instead of bytes
+    pub sgr_mouse: bool,
+}
+
+/// Mouse tracking mode set by the application, each one reports the events of the previous ones too
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum MouseTracking {
+    #[default]
+    None,
+    /// Presses only, without modifiers (`CSI ? 9 h`)
+    X10,
+    /// Presses, releases and the wheel (`CSI ? 1000 h`)
+    Normal,
+    /// Moves while a button is pressed (`CSI ? 1002 h`)
+    ButtonEvent,
+    /// Every move (`CSI ? 1003 h`)
+    AnyEvent,
+}
+
+impl InputModes {
+    /// Disambiguate escape codes
+    pub const KITTY_DISAMBIGUATE: u8 = 0b1;
+    /// Report all keys as escape codes
+    pub const KITTY_ALL_KEYS: u8 = 0b1000;
+}
+
+#[derive(Debug, Clone)]
+struct Line {
+    cells: Vec<Character>,
+    /// The text reached the right margin and continues on the next line
+    wrapped: bool,
+}
+
+impl Line {
+    fn new(width: u32, blank: Character) -> Self {
+        Self {
+            cells: vec![blank; width as usize],
+            wrapped: false,
+        }
+    }
+
+    fn is_blank(&self, blank: Character) -> bool {
+        !self.wrapped && self.cells.iter().all(|cell| *cell == blank)
     }
 }
 
@@ -31,29 +172,68 @@ pub struct TerminalParser {
     width: u32,
     height: u32,
     state: TerminalState,
+    screen: Vec<Line>,
+    scrollback: VecDeque<Line>,
+    cursor_visible: bool,
+    input_modes: InputModes,
+    /// Previous kitty keyboard flags, restored when the application pops its own
+    keyboard_flags_stack: Vec<u8>,
+    /// Replies to queries, waiting to be sent to the application
+    responses: Vec<u8>,
+    /// Window title set by the application with OSC 0 or OSC 2
+    title: Option<String>,
+    /// First and last rows scrolled by line feeds and reverse index (DECSTBM)
+    scroll_top: i32,
+    scroll_bottom: i32,
+    /// Main screen with its size and cursor, kept while the alternate screen is shown
+    main_screen: Option<(Vec<Line>, u32, u32, TerminalState)>,
+    /// Cursor and attributes saved by DECSC
+    saved_state: Option<TerminalState>,
+    /// Last printed character, repeated by REP
+    last_character: Option<char>,
 }
 
 impl TerminalParser {
     pub fn new(width: u32, height: u32, default_background_color: Color) -> Self {
+        let width = width.max(1);
+        let height = height.max(1);
+
+        let state = TerminalState {
+            default_foreground_color: Color::RGB(255, 255, 255),
+            default_background_color,
+            foreground: Color::RGB(255, 255, 255),
+            background: default_background_color,
+            bold: false,
+            dim: false,
+            italic: false,
+            underline: false,
+            inverse: false,
+            cursor_x: 0,
+            cursor_y: 0,
+            charsets: [Charset::Ascii; 2],
+            shift_out: false,
+        };
+
         Self {
             width,
             height,
-            state: TerminalState {
-                default_foreground_color: Color::RGB(255, 255, 255),
-                default_background_color,
-                foreground: Color::RGB(255, 255, 255),
-                background: default_background_color,
-                bold: false,
-                dim: false,
-                italic: false,
-                underline: false,
-                cursor_x: 0,
-                cursor_y: 0,
-            }
+            screen: vec![Line::new(width, state.blank()); height as usize],
+            scrollback: VecDeque::new(),
+            cursor_visible: true,
+            input_modes: InputModes::default(),
+            keyboard_flags_stack: Vec::new(),
+            responses: Vec::new(),
+            title: None,
+            scroll_top: 0,
+            scroll_bottom: height as i32 - 1,
+            main_screen: None,
+            saved_state: None,
+            last_character: None,
+            state,
         }
     }
 
-    pub fn parse_to_surface(&mut self, data: &[u8], mut surface: Surface) -> Surface {
+    pub fn parse(&mut self, data: &[u8]) {
         let text = String::from_utf8_lossy(data);
         let chars: Vec<char> = text.chars().collect();
 
@@ -63,29 +243,203 @@ impl TerminalParser {
             if chars[i] == '\u{1b}' && i + 1 < chars.len() && chars[i + 1] == '[' {
                 // Re-encode remaining chars into bytes for ANSI parsing
                 let slice: String = chars[i..].iter().collect();
-                let consumed = self.parse_ansi_sequence(slice.as_bytes(), &mut surface);
+                let consumed = self.parse_ansi_sequence(slice.as_bytes());
                 // convert consumed bytes -> consumed chars
                 let consumed_chars = String::from_utf8_lossy(&slice.as_bytes()[..consumed])
                     .chars()
                     .count();
                 i += consumed_chars;
             }
+            else if chars[i] == '\u{1b}' && i + 1 < chars.len() && chars[i + 1] == ']' {
+                i += self.parse_osc_sequence(&chars[i..]);
+            }
+            else if chars[i] == '\u{1b}' && i + 1 < chars.len() {
+                i += self.handle_escape(&chars[i + 1..]);
+            }
             else {
                 // Handle regular character
-                self.write_character(chars[i], &mut surface);
+                self.write_character(chars[i]);
                 i += 1;
             }
         }
-        
+    }
+
+    pub fn input_modes(&self) -> InputModes {
+        self.input_modes
+    }
+
+    /// Window title set by the application
+    pub fn title(&self) -> Option<&str> {
+        self.title.as_deref()
+    }
+
+    /// Last non-empty lines of the screen as plain text, truncated to the given width
+    pub fn preview(&self, width: usize, height: usize) -> Vec<String> {
+        let blank = self.state.blank();
+        let end = self.screen
+            .iter()
+            .rposition(|line| !line.is_blank(blank))
+            .map_or(0, |index| index + 1);
+
+        self.screen[end.saturating_sub(height)..end]
+            .iter()
+            .map(|line| {
+                let text: String = line.cells.iter().take(width).map(|cell| cell.code).collect();
+                text.trim_end().to_string()
+            })
+            .collect()
+    }
+
+    /// Take the replies to the queries parsed so far
+    pub fn take_responses(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.responses)
+    }
+
+    /// Draw the visible screen and the cursor onto a new surface
+    pub fn render(&self) -> Surface {
+        let mut surface = Surface::new(self.width, self.height);
+
+        for (y, line) in self.screen.iter().enumerate() {
+            for (x, cell) in line.cells.iter().enumerate() {
+                surface.write_char(x as i32, y as i32, *cell);
+            }
+        }
+
+        if self.cursor_visible {
+            surface.set_cursor(self.state.cursor_x.min(self.width as i32 - 1), self.state.cursor_y);
+        }
+        else {
+            surface.hide_cursor();
+        }
+
         surface
     }
 
+    /// Rewrap soft-wrapped lines of the screen and the scrollback to the new width, keeping the cursor on the same character
     pub fn resize(&mut self, width: u32, height: u32) {
+        let width = width.max(1);
+        let height = height.max(1);
+
+        if width == self.width && height == self.height {
+            return;
+        }
+
+        self.scroll_top = 0;
+        self.scroll_bottom = height as i32 - 1;
+
+        // Applications showing the alternate screen redraw it, the main screen is rewrapped when it is shown again
+        if self.main_screen.is_some() {
+            let blank = self.state.blank();
+            for line in &mut self.screen {
+                line.cells.resize(width as usize, blank);
+                line.wrapped = false;
+            }
+            self.screen.resize(height as usize, Line::new(width, blank));
+
+            self.width = width;
+            self.height = height;
+            self.state.cursor_x = self.state.cursor_x.min(width as i32 - 1);
+            self.state.cursor_y = self.state.cursor_y.min(height as i32 - 1);
+            return;
+        }
+
+        let blank = self.state.blank();
+        let cursor_row = self.scrollback.len() + self.state.cursor_y as usize;
+        let cursor_x = self.state.cursor_x.max(0) as usize;
+
+        // Keep the rows up to the cursor or the last non-empty row, whichever is lower
+        let mut rows: Vec<Line> = self.scrollback.drain(..).chain(self.screen.drain(..)).collect();
+        while rows.len() > cursor_row + 1 && rows.last().is_some_and(|line| line.is_blank(blank)) {
+            rows.pop();
+        }
+
+        // Join soft-wrapped rows back into logical lines
+        let mut logical_lines: Vec<Vec<Character>> = Vec::new();
+        let mut cursor_line = 0;
+        let mut cursor_offset = 0;
+        let mut continues = false;
+
+        for (index, mut row) in rows.into_iter().enumerate() {
+            if !continues {
+                logical_lines.push(Vec::new());
+            }
+
+            let line_index = logical_lines.len() - 1;
+            let cells = &mut logical_lines[line_index];
+
+            if index == cursor_row {
+                cursor_line = line_index;
+                cursor_offset = cells.len() + cursor_x;
+            }
+
+            if !row.wrapped {
+                while row.cells.last() == Some(&blank) {
+                    row.cells.pop();
+                }
+            }
+
+            cells.extend(row.cells);
+            continues = row.wrapped;
+        }
+
+        // Split the logical lines at the new width
+        let mut rows: Vec<Line> = Vec::new();
+        let mut new_cursor = (0, 0);
+
+        for (index, cells) in logical_lines.into_iter().enumerate() {
+            let first_row = rows.len();
+            let chunks: Vec<&[Character]> = match cells.is_empty() {
+                true => vec![&[]],
+                false => cells.chunks(width as usize).collect()
+            };
+            let count = chunks.len();
+
+            for (chunk_index, chunk) in chunks.into_iter().enumerate() {
+                let mut line = Line::new(width, blank);
+                line.cells[..chunk.len()].copy_from_slice(chunk);
+                line.wrapped = chunk_index + 1 < count;
+                rows.push(line);
+            }
+
+            if index == cursor_line {
+                let mut row = cursor_offset / width as usize;
+                let mut column = cursor_offset % width as usize;
+
+                // A cursor sitting right after the last character stays on that row, waiting to wrap
+                if column == 0 && row > 0 && cursor_offset >= cells.len() {
+                    row -= 1;
+                    column = width as usize;
+                }
+
+                while first_row + row >= rows.len() {
+                    rows.push(Line::new(width, blank));
+                }
+
+                new_cursor = (first_row + row, column);
+            }
+        }
+
+        let (cursor_row, cursor_column) = new_cursor;
+        let end = rows.len().max(cursor_row + 1);
+        let start = end.saturating_sub(height as usize).min(cursor_row);
+
+        let mut screen = rows.split_off(start);
+        screen.truncate(height as usize);
+        screen.resize(height as usize, Line::new(width, blank));
+
+        self.scrollback = rows.into();
+        while self.scrollback.len() > SCROLLBACK_LIMIT {
+            self.scrollback.pop_front();
+        }
+
+        self.screen = screen;
         self.width = width;
         self.height = height;
+        self.state.cursor_x = cursor_column as i32;
+        self.state.cursor_y = (cursor_row - start) as i32;
     }
 
-    fn parse_ansi_sequence(&mut self, data: &[u8], surface: &mut Surface) -> usize {
+    fn parse_ansi_sequence(&mut self, data: &[u8]) -> usize {
         if data.len() < 3 {
             return 1; // Skip invalid sequence
         }
@@ -93,14 +447,16 @@ impl TerminalParser {
         let mut i = 2; // Skip '\x1b['
         let mut params = Vec::new();
         let mut current_param = String::new();
-        let mut private_mode = false;
+        let mut prefix = None;
 
-        // Handle private mode prefix '?'
-        if i < data.len() && data[i] == b'?' {
-            private_mode = true;
+        // Handle private mode prefix '?' and the '<', '=', '>' prefixes
+        if i < data.len() && matches!(data[i], b'?' | b'<' | b'=' | b'>') {
+            prefix = Some(data[i]);
             i += 1;
         }
 
+        let mut intermediate = false;
+
         // Parse parameters
         while i < data.len() {
             let byte = data[i];
@@ -110,16 +466,18 @@ impl TerminalParser {
                     params.push(current_param.parse::<u32>().unwrap_or(0));
                     current_param.clear();
                 }
-                b'A'..=b'Z' | b'a'..=b'z' => {
+                // Intermediate bytes such as the space of DECSCUSR, these sequences are ignored
+                b' '..=b'/' => intermediate = true,
+                b'@'..=b'~' => {
                     // End of sequence
                     if !current_param.is_empty() {
                         params.push(current_param.parse::<u32>().unwrap_or(0));
                     }
-                    if private_mode {
-                        self.handle_private_ansi_command(byte as char, &params, surface);
-                    }
-                    else {
-                        self.handle_ansi_command(byte as char, &params, surface);
+                    match (prefix, intermediate) {
+                        (_, true) => {}
+                        (None, false) => self.handle_ansi_command(byte as char, &params),
+                        (Some(b'?'), false) => self.handle_private_ansi_command(byte as char, &params),
+                        (Some(prefix), false) => self.handle_keyboard_mode_command(prefix, byte as char, &params),
                     }
                     return i + 1;
                 }
@@ -131,34 +489,161 @@ impl TerminalParser {
         1 // Skip if we couldn't parse
     }
 
-    fn handle_ansi_command(&mut self, command: char, params: &[u32], surface: &mut Surface) {
+    /// Parse an OSC sequence terminated by BEL or ST, returns the number of chars consumed
+    fn parse_osc_sequence(&mut self, chars: &[char]) -> usize {
+        let mut end = 2; // Skip '\x1b]'
+
+        let terminator_length = loop {
+            match chars.get(end) {
+                Some('\u{7}') => break 1,
+                Some('\u{1b}') if chars.get(end + 1) == Some(&'\\') => break 2,
+                Some(_) => end += 1,
+                // Unterminated, drop the rest of the data
+                None => return chars.len(),
+            }
+        };
+
+        let content: String = chars[2..end].iter().collect();
+        if let Some((command, text)) = content.split_once(';') {
+            // Icon name and window title, or window title only
+            if command == "0" || command == "2" {
+                self.title = match text.trim() {
+                    "" => None,
+                    title => Some(title.to_string()),
+                };
+            }
+        }
+
+        end + terminator_length
+    }
+
+    fn handle_ansi_command(&mut self, command: char, params: &[u32]) {
         match command {
             'H' | 'f' => {
-                // Cursor position
-                let row = params.get(0).unwrap_or(&1).saturating_sub(1) as i32;
-                let col = params.get(1).unwrap_or(&1).saturating_sub(1) as i32;
-                self.state.cursor_x = col.min(self.width as i32 - 1);
-                self.state.cursor_y = row.min(self.height as i32 - 1);
+                // Cursor position, 1-based
+                let row = params.first().copied().unwrap_or(1).max(1) - 1;
+                let col = params.get(1).copied().unwrap_or(1).max(1) - 1;
+                self.state.cursor_x = col.min(self.width - 1) as i32;
+                self.state.cursor_y = row.min(self.height - 1) as i32;
             }
             'A' => {
                 // Cursor up
-                let count = params.get(0).unwrap_or(&1);
-                self.state.cursor_y = (self.state.cursor_y - *count as i32).max(0);
+                self.state.cursor_y = self.state.cursor_y.saturating_sub(movement(params)).max(0);
             }
             'B' => {
                 // Cursor down
-                let count = params.get(0).unwrap_or(&1);
-                self.state.cursor_y = (self.state.cursor_y + *count as i32).min(self.height as i32 - 1);
+                self.state.cursor_y = self.state.cursor_y.saturating_add(movement(params)).min(self.height as i32 - 1);
             }
             'C' => {
                 // Cursor right
-                let count = params.get(0).unwrap_or(&1);
-                self.state.cursor_x = (self.state.cursor_x + *count as i32).min(self.width as i32 - 1);
+                self.state.cursor_x = self.state.cursor_x.saturating_add(movement(params)).min(self.width as i32 - 1);
             }
             'D' => {
-                // Cursor left
-                let count = params.get(0).unwrap_or(&1);
-                self.state.cursor_x = (self.state.cursor_x - *count as i32).max(0);
+                // Cursor left, from the last column when a wrap is pending
+                let x = self.state.cursor_x.min(self.width as i32 - 1);
+                self.state.cursor_x = x.saturating_sub(movement(params)).max(0);
+            }
+            'E' | 'F' => {
+                // Cursor to the first column of a next or previous line
+                let y = match command {
+                    'E' => self.state.cursor_y.saturating_add(movement(params)),
+                    _ => self.state.cursor_y.saturating_sub(movement(params)),
+                };
+                self.state.cursor_x = 0;
+                self.state.cursor_y = y.clamp(0, self.height as i32 - 1);
+            }
+            'G' | '`' => {
+                // Cursor column, 1-based
+                self.state.cursor_x = movement(params).min(self.width as i32) - 1;
+            }
+            'd' => {
+                // Cursor row, 1-based
+                self.state.cursor_y = movement(params).min(self.height as i32) - 1;
+            }
+            'r' => {
+                // Scrolling region, 1-based and inclusive, the whole screen when invalid
+                let top = params.first().copied().unwrap_or(1).max(1) as i32 - 1;
+                let bottom = match params.get(1).copied().unwrap_or(0) {
+                    0 => self.height as i32,
+                    bottom => bottom.min(self.height) as i32,
+                } - 1;
+                (self.scroll_top, self.scroll_bottom) = match top < bottom {
+                    true => (top, bottom),
+                    false => (0, self.height as i32 - 1),
+                };
+                self.state.cursor_x = 0;
+                self.state.cursor_y = 0;
+            }
+            'L' | 'M' => {
+                // Insert or delete lines at the cursor, inside the scrolling region
+                let y = self.state.cursor_y;
+                if (self.scroll_top..=self.scroll_bottom).contains(&y) {
+                    match command {
+                        'L' => self.insert_lines(y, movement(params)),
+                        _ => _ = self.delete_lines(y, movement(params)),
+                    }
+                    self.state.cursor_x = 0;
+                }
+            }
+            'S' => {
+                // Scroll up
+                self.scroll_up(movement(params));
+            }
+            'T' => {
+                // Scroll down
+                self.insert_lines(self.scroll_top, movement(params));
+            }
+            '@' | 'P' => {
+                // Insert or delete characters at the cursor, shifting the rest of the line
+                let erased = self.state.erased();
+                let width = self.width as usize;
+                let x = self.state.cursor_x.min(self.width as i32 - 1) as usize;
+                let count = (movement(params) as usize).min(width - x);
+
+                if let Some(line) = self.screen.get_mut(self.state.cursor_y as usize) {
+                    match command {
+                        '@' => {
+                            line.cells.splice(x..x, std::iter::repeat_n(erased, count));
+                            line.cells.truncate(width);
+                        }
+                        _ => {
+                            line.cells.drain(x..x + count);
+                            line.cells.resize(width, erased);
+                        }
+                    }
+                    line.wrapped = false;
+                }
+            }
+            'X' => {
+                // Erase characters from the cursor
+                let x = self.state.cursor_x;
+                self.erase_cells(self.state.cursor_y, x, x.saturating_add(movement(params)));
+            }
+            'b' => {
+                // Repeat the last character, at most enough to fill the screen
+                if let Some(character) = self.last_character {
+                    let count = movement(params).min((self.width * self.height) as i32);
+                    for _ in 0..count {
+                        self.write_character(character);
+                    }
+                }
+            }
+            's' if params.is_empty() => {
+                // Save cursor
+                self.saved_state = Some(self.state);
+            }
+            'u' if params.is_empty() => {
+                // Restore cursor
+                self.restore_state();
+            }
+            'n' => {
+                // Device status report
+                let response = match params.first().copied().unwrap_or(0) {
+                    5 => "\x1B[0n".to_string(),
+                    6 => format!("\x1B[{};{}R", self.state.cursor_y + 1, self.state.cursor_x.min(self.width as i32 - 1) + 1),
+                    _ => return,
+                };
+                self.responses.extend_from_slice(response.as_bytes());
             }
             'm' => {
                 // SGR (Select Graphic Rendition) - colors and attributes
@@ -172,13 +657,17 @@ impl TerminalParser {
             }
             'J' => {
                 // Clear screen
-                let mode = params.get(0).copied().unwrap_or(0);
-                self.handle_erase_display(mode, surface);
+                let mode = params.first().copied().unwrap_or(0);
+                self.handle_erase_display(mode);
             }
             'K' => {
                 // Clear line
-                let mode = params.get(0).copied().unwrap_or(0);
-                self.handle_erase_line(mode, surface);
+                let mode = params.first().copied().unwrap_or(0);
+                self.handle_erase_line(mode);
+            }
+            'c' if params.first().copied().unwrap_or(0) == 0 => {
+                // Primary device attributes
+                self.responses.extend_from_slice(DEVICE_ATTRIBUTES);
             }
             _ => {
                 // Ignore unknown sequences
@@ -186,74 +675,147 @@ impl TerminalParser {
         }
     }
 
-    fn handle_private_ansi_command(&mut self, command: char, _params: &[u32], surface: &mut Surface) {
-        match command {
-            'l' => {
-                // Hide cursor
-                surface.hide_cursor()
-            }
-            'h' => {
-                // Show cursor
-                surface.set_cursor(self.state.cursor_x, self.state.cursor_y);
+    fn handle_private_ansi_command(&mut self, command: char, params: &[u32]) {
+        let enable = match command {
+            'h' => true,
+            'l' => false,
+            'u' => {
+                // Query kitty keyboard flags
+                let response = format!("\x1B[?{}u", self.input_modes.kitty_keyboard_flags);
+                self.responses.extend_from_slice(response.as_bytes());
+                return;
             }
             _ => {
                 // ignore unknown private sequences
+                return;
+            }
+        };
+
+        for param in params {
+            match param {
+                // Application cursor keys
+                1 => self.input_modes.application_cursor_keys = enable,
+                // Show or hide cursor
+                25 => self.cursor_visible = enable,
+                // Mouse tracking, disabling any mode stops it
+                9 | 1000 | 1002 | 1003 => self.input_modes.mouse_tracking = match (enable, param) {
+                    (false, _) => MouseTracking::None,
+                    (true, 9) => MouseTracking::X10,
+                    (true, 1000) => MouseTracking::Normal,
+                    (true, 1002) => MouseTracking::ButtonEvent,
+                    (true, _) => MouseTracking::AnyEvent,
+                },
+                1006 => self.input_modes.sgr_mouse = enable,
+                // Alternate screen, 1049 also saves the cursor and clears the screen
+                47 | 1047 | 1049 => self.set_alternate_screen(enable, *param == 1049),
+                _ => {}
             }
         }
     }
 
-    fn handle_erase_display(&mut self, param: u32, surface: &mut Surface) {
+    fn handle_keyboard_mode_command(&mut self, prefix: u8, command: char, params: &[u32]) {
+        match (prefix, command) {
+            (b'>', 'u') => {
+                // Push kitty keyboard flags
+                self.keyboard_flags_stack.push(self.input_modes.kitty_keyboard_flags);
+                if self.keyboard_flags_stack.len() > KEYBOARD_FLAGS_STACK_LIMIT {
+                    self.keyboard_flags_stack.remove(0);
+                }
+                self.input_modes.kitty_keyboard_flags = params.first().copied().unwrap_or(0) as u8;
+            }
+            (b'<', 'u') => {
+                // Pop kitty keyboard flags
+                // Popping more entries than pushed empties the stack and resets the flags
+                let count = params.first().copied().unwrap_or(1).max(1) as usize;
+                let remaining = self.keyboard_flags_stack.len().saturating_sub(count);
+                self.input_modes.kitty_keyboard_flags = match count > self.keyboard_flags_stack.len() {
+                    true => 0,
+                    false => self.keyboard_flags_stack[remaining],
+                };
+                self.keyboard_flags_stack.truncate(remaining);
+            }
+            (b'=', 'u') => {
+                // Set kitty keyboard flags: 1 replaces, 2 adds, 3 removes
+                let flags = params.first().copied().unwrap_or(0) as u8;
+                let current = self.input_modes.kitty_keyboard_flags;
+                self.input_modes.kitty_keyboard_flags = match params.get(1).copied().unwrap_or(1) {
+                    2 => current | flags,
+                    3 => current & !flags,
+                    _ => flags,
+                };
+            }
+            (b'>', 'm') if params.first() == Some(&4) => {
+                // xterm modifyOtherKeys
+                self.input_modes.modify_other_keys = params.get(1).copied().unwrap_or(0) as u8;
+            }
+            _ => {
+                // ignore other sequences
+            }
+        }
+    }
+
+    fn handle_erase_display(&mut self, param: u32) {
         match param {
             0 => {
                 // clear from cursor to end of screen
-                for y in self.state.cursor_y..self.height as i32 {
-                    let start_x = if y == self.state.cursor_y { self.state.cursor_x } else { 0 };
-                    for x in start_x..self.width as i32 {
-                        surface.write_char(x, y, Character::new(' ', self.state.foreground, self.state.background, CharFlags::None));
-                    }
+                self.handle_erase_line(0);
+                for y in (self.state.cursor_y + 1)..self.height as i32 {
+                    self.erase_cells(y, 0, self.width as i32);
                 }
             }
             1 => {
                 // clear from beginning of screen to cursor
-                for y in 0..=self.state.cursor_y {
-                    let end_x = if y == self.state.cursor_y { self.state.cursor_x } else { self.width as i32 - 1 };
-                    for x in 0..=end_x {
-                        surface.write_char(x, y, Character::new(' ', self.state.foreground, self.state.background, CharFlags::None));
-                    }
+                for y in 0..self.state.cursor_y {
+                    self.erase_cells(y, 0, self.width as i32);
                 }
+                self.handle_erase_line(1);
             }
             2 => {
                 // clear entire screen
-                surface.clear(Character::new(' ', self.state.foreground, self.state.background, CharFlags::None));
+                for y in 0..self.height as i32 {
+                    self.erase_cells(y, 0, self.width as i32);
+                }
             }
             _ => {}
         }
     }
 
-    fn handle_erase_line(&mut self, param: u32, surface: &mut Surface) {
+    fn handle_erase_line(&mut self, param: u32) {
+        let y = self.state.cursor_y;
+
         match param {
             0 => {
                 // clear from cursor to end of line
-                for x in self.state.cursor_x..self.width as i32 {
-                    surface.write_char(x, self.state.cursor_y, Character::new(' ', self.state.foreground, self.state.background, CharFlags::None));
-                }
+                self.erase_cells(y, self.state.cursor_x, self.width as i32);
             }
             1 => {
                 // clear from beginning of line to cursor
-                for x in 0..=self.state.cursor_x {
-                    surface.write_char(x, self.state.cursor_y, Character::new(' ', self.state.foreground, self.state.background, CharFlags::None));
-                }
+                self.erase_cells(y, 0, self.state.cursor_x + 1);
             }
             2 => {
                 // clear entire line
-                for x in 0..self.width as i32 {
-                    surface.write_char(x, self.state.cursor_y, Character::new(' ', self.state.foreground, self.state.background, CharFlags::None));
-                }
+                self.erase_cells(y, 0, self.width as i32);
             }
             _ => {}
         }
     }
 
+    /// Erase cells in `[from, to)` of a screen line, which also breaks a soft wrap when reaching its end
+    fn erase_cells(&mut self, y: i32, from: i32, to: i32) {
+        let erased = self.state.erased();
+        let width = self.width as i32;
+
+        if let Some(line) = self.screen.get_mut(y as usize) {
+            for x in from.clamp(0, width)..to.clamp(0, width) {
+                line.cells[x as usize] = erased;
+            }
+
+            if to >= width {
+                line.wrapped = false;
+            }
+        }
+    }
+
     fn handle_sgr_params(&mut self, params: &[u32]) {
         let mut iter = params.iter().copied().peekable();
 
@@ -270,6 +832,8 @@ impl TerminalParser {
                 }
                 23 => self.state.italic = false,
                 24 => self.state.underline = false,
+                7 => self.state.inverse = true,
+                27 => self.state.inverse = false,
 
                 39 => self.state.foreground = self.state.default_foreground_color,
                 49 => self.state.background = self.state.default_background_color,
@@ -321,34 +885,34 @@ impl TerminalParser {
         }
     }
 
-    fn write_character(&mut self, ch: char, surface: &mut Surface) {
+    fn write_character(&mut self, ch: char) {
         match ch {
             '\r' => {
                 self.state.cursor_x = 0;
             }
             '\n' => {
                 self.state.cursor_x = 0;
-                self.state.cursor_y += 1;
-                if self.state.cursor_y >= self.height as i32 {
-                    self.state.cursor_y = self.height as i32 - 1;
-                }
+                self.line_feed();
             }
             '\t' => {
                 // Tab to next 8-character boundary
-                self.state.cursor_x = ((self.state.cursor_x / 8) + 1) * 8;
-                self.cursor_forward()
+                self.state.cursor_x = (((self.state.cursor_x / 8) + 1) * 8).min(self.width as i32 - 1);
             }
             '\x08' => {
                 // Backspace
+                self.state.cursor_x = self.state.cursor_x.min(self.width as i32 - 1);
                 if self.state.cursor_x > 0 {
                     self.state.cursor_x -= 1;
                 }
             }
+            '\x0E' => self.state.shift_out = true,
+            '\x0F' => self.state.shift_out = false,
             c if c.is_control() => {
                 // Ignore other control characters
             }
             c => {
                 // Regular printable character
+                let c = self.state.charsets[usize::from(self.state.shift_out)].map(c);
                 let mut flags = CharFlags::None;
                 if self.state.bold {
                     flags |= CharFlags::Bold;
@@ -363,24 +927,170 @@ impl TerminalParser {
                     flags |= CharFlags::Underline;
                 }
 
-                let character = Character::new(c, self.state.foreground, self.state.background, flags);
-                surface.write_char(self.state.cursor_x, self.state.cursor_y, character);
+                self.state.cursor_x = self.state.cursor_x.max(0);
+                self.state.cursor_y = self.state.cursor_y.clamp(0, self.height as i32 - 1);
+
+                // The previous character filled the line, continue on the next one
+                if self.state.cursor_x >= self.width as i32 {
+                    self.screen[self.state.cursor_y as usize].wrapped = true;
+                    self.state.cursor_x = 0;
+                    self.line_feed();
+                }
+
+                let (foreground, background) = match self.state.inverse {
+                    true => (self.state.background, self.state.foreground),
+                    false => (self.state.foreground, self.state.background),
+                };
+                let character = Character::new(c, foreground, background, flags);
+                self.screen[self.state.cursor_y as usize].cells[self.state.cursor_x as usize] = character;
+                self.last_character = Some(c);
                 self.cursor_forward();
             }
         }
     }
 
     pub fn cursor_forward(&mut self) {
-        // Advance cursor
-        self.state.cursor_x += 1;
-        if self.state.cursor_x >= self.width as i32 {
-            self.state.cursor_x = 0;
+        // Advance cursor, the wrap is deferred until the next character is written
+        if self.state.cursor_x < self.width as i32 {
+            self.state.cursor_x += 1;
+        }
+    }
+
+    /// Move the cursor one line down, scrolling the region when at its bottom
+    fn line_feed(&mut self) {
+        if self.state.cursor_y == self.scroll_bottom {
+            self.scroll_up(1);
+        }
+        else if self.state.cursor_y + 1 < self.height as i32 {
             self.state.cursor_y += 1;
-            if self.state.cursor_y >= self.height as i32 {
-                self.state.cursor_y = self.height as i32 - 1;
+        }
+    }
+
+    /// Move the cursor one line up, scrolling the region down when at its top
+    fn reverse_index(&mut self) {
+        if self.state.cursor_y == self.scroll_top {
+            self.insert_lines(self.scroll_top, 1);
+        }
+        else if self.state.cursor_y > 0 {
+            self.state.cursor_y -= 1;
+        }
+    }
+
+    /// Scroll the region up, the top lines of the main screen go to the scrollback
+    fn scroll_up(&mut self, count: i32) {
+        let lines = self.delete_lines(self.scroll_top, count);
+
+        if self.scroll_top == 0 && self.main_screen.is_none() {
+            self.scrollback.extend(lines);
+            while self.scrollback.len() > SCROLLBACK_LIMIT {
+                self.scrollback.pop_front();
             }
         }
     }
+
+    /// Insert blank lines at `top`, pushing the following ones out of the bottom of the scrolling region
+    fn insert_lines(&mut self, top: i32, count: i32) {
+        let bottom = self.scroll_bottom as usize + 1;
+        let count = (count as usize).min(bottom - top as usize);
+        let lines = vec![Line::new(self.width, self.state.erased()); count];
+
+        self.screen.drain(bottom - count..bottom);
+        self.screen.splice(top as usize..top as usize, lines);
+        self.break_wrap_above(top);
+    }
+
+    /// Remove lines from `top`, pulling the following ones up and adding blank lines at the bottom of the scrolling region
+    fn delete_lines(&mut self, top: i32, count: i32) -> Vec<Line> {
+        let bottom = self.scroll_bottom as usize + 1;
+        let count = (count as usize).min(bottom - top as usize);
+        let lines = vec![Line::new(self.width, self.state.erased()); count];
+
+        let removed = self.screen.drain(top as usize..top as usize + count).collect();
+        self.screen.splice(bottom - count..bottom - count, lines);
+        self.break_wrap_above(top);
+        removed
+    }
+
+    /// The line above `y` no longer continues on the line now at `y`
+    fn break_wrap_above(&mut self, y: i32) {
+        if y > 0 {
+            self.screen[y as usize - 1].wrapped = false;
+        }
+    }
+
+    fn restore_state(&mut self) {
+        if let Some(state) = self.saved_state {
+            self.state = state;
+            self.state.cursor_x = self.state.cursor_x.min(self.width as i32 - 1);
+            self.state.cursor_y = self.state.cursor_y.min(self.height as i32 - 1);
+        }
+    }
+
+    /// Switch to a blank alternate screen without scrollback, or back to the main screen rewrapped to the current size
+    fn set_alternate_screen(&mut self, enable: bool, save_cursor: bool) {
+        match (enable, self.main_screen.take()) {
+            (true, None) => {
+                let screen = vec![Line::new(self.width, self.state.blank()); self.height as usize];
+                let main_screen = std::mem::replace(&mut self.screen, screen);
+                self.main_screen = Some((main_screen, self.width, self.height, self.state));
+            }
+            (false, Some((screen, width, height, state))) => {
+                let (current_width, current_height) = (self.width, self.height);
+                let (cursor_x, cursor_y) = (self.state.cursor_x, self.state.cursor_y);
+
+                self.screen = screen;
+                self.width = width;
+                self.height = height;
+                if save_cursor {
+                    self.state = state;
+                }
+                else {
+                    self.state.cursor_x = cursor_x.min(width as i32 - 1);
+                    self.state.cursor_y = cursor_y.min(height as i32 - 1);
+                }
+
+                self.resize(current_width, current_height);
+            }
+            (_, main_screen) => self.main_screen = main_screen,
+        }
+    }
+
+    /// Handle an escape sequence other than CSI and OSC, returns the number of characters consumed including the escape
+    fn handle_escape(&mut self, chars: &[char]) -> usize {
+        match chars[0] {
+            '7' => self.saved_state = Some(self.state),
+            '8' => self.restore_state(),
+            'D' => self.line_feed(),
+            'E' => {
+                self.state.cursor_x = 0;
+                self.line_feed();
+            }
+            'M' => self.reverse_index(),
+            'c' => {
+                // Full reset, the scrollback is kept
+                let scrollback = std::mem::take(&mut self.scrollback);
+                *self = Self::new(self.width, self.height, self.state.default_background_color);
+                self.scrollback = scrollback;
+            }
+            // G0 and G1 character sets, G2 and G3 are never shifted in
+            '(' | ')' if chars.len() >= 2 => {
+                self.state.charsets[usize::from(chars[0] == ')')] = Charset::designated(chars[1]);
+                return 3;
+            }
+            // G2 and G3 character sets and line size, the final character is ignored too
+            '(' | ')' | '*' | '+' | '#' | '%' => return chars.len().min(2) + 1,
+            // DECKPAM / DECKPNM are ignored since AppCUI reports keypad keys like their main keyboard counterparts,
+            // other sequences are not supported
+            _ => {}
+        }
+
+        2
+    }
+}
+
+/// Count of a cursor movement, 0 and a missing parameter move by one
+fn movement(params: &[u32]) -> i32 {
+    params.first().copied().unwrap_or(1).clamp(1, i32::MAX as u32) as i32
 }
 
 /// Map 16 ANSI colors to RGB
@@ -399,9 +1109,9 @@ fn ansi_16_color(code: u32, bright: bool) -> Color {
 
     if bright {
         Color::RGB(
-            r.saturating_mul(2).min(255),
-            g.saturating_mul(2).min(255),
-            b.saturating_mul(2).min(255)
+            r.saturating_mul(2),
+            g.saturating_mul(2),
+            b.saturating_mul(2)
         )
     }
     else {
@@ -441,3 +1151,368 @@ fn ansi_256_color(idx: u32) -> Color {
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parser(width: u32, height: u32, data: &str) -> TerminalParser {
+        let mut parser = TerminalParser::new(width, height, Color::RGB(0, 0, 0));
+        parser.parse(data.as_bytes());
+        parser
+    }
+
+    fn cursor(parser: &TerminalParser) -> (i32, i32) {
+        (parser.state.cursor_x, parser.state.cursor_y)
+    }
+
+    fn lines(parser: &TerminalParser) -> Vec<String> {
+        parser.screen
+            .iter()
+            .map(|line| line.cells.iter().map(|cell| cell.code).collect::<String>().trim_end().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn moves_cursor() {
+        let cases: &[(&str, (i32, i32))] = &[
+            // Cursor position
+            ("\x1B[H", (0, 0)),
+            ("\x1B[3;4H", (3, 2)),
+            ("\x1B[0;0H", (0, 0)),
+            ("\x1B[3f", (0, 2)),
+            ("\x1B[;5H", (4, 0)),
+            ("\x1B[99;99H", (9, 4)),
+            ("\x1B[4294967295;4294967295H", (9, 4)),
+            // Cursor up, down, right and left
+            ("\x1B[3;4H\x1B[A", (3, 1)),
+            ("\x1B[3;4H\x1B[0A", (3, 1)),
+            ("\x1B[3;4H\x1B[2A", (3, 0)),
+            ("\x1B[3;4H\x1B[99A", (3, 0)),
+            ("\x1B[3;4H\x1B[4294967295A", (3, 0)),
+            ("\x1B[3;4H\x1B[B", (3, 3)),
+            ("\x1B[3;4H\x1B[0B", (3, 3)),
+            ("\x1B[3;4H\x1B[4294967295B", (3, 4)),
+            ("\x1B[3;4H\x1B[C", (4, 2)),
+            ("\x1B[3;4H\x1B[0C", (4, 2)),
+            ("\x1B[3;4H\x1B[4294967295C", (9, 2)),
+            ("\x1B[3;4H\x1B[D", (2, 2)),
+            ("\x1B[3;4H\x1B[0D", (2, 2)),
+            ("\x1B[3;4H\x1B[4294967295D", (0, 2)),
+            // Pending wrap after the last column
+            ("0123456789\x1B[D", (8, 0)),
+            ("0123456789\x1B[C", (9, 0)),
+            // Next and previous line, column and row
+            ("\x1B[3;4H\x1B[E", (0, 3)),
+            ("\x1B[3;4H\x1B[9E", (0, 4)),
+            ("\x1B[3;4H\x1B[2F", (0, 0)),
+            ("\x1B[3;4H\x1B[7G", (6, 2)),
+            ("\x1B[3;4H\x1B[99`", (9, 2)),
+            ("\x1B[3;4H\x1B[0G", (0, 2)),
+            ("\x1B[3;4H\x1B[2d", (3, 1)),
+            ("\x1B[3;4H\x1B[99d", (3, 4)),
+            // Save and restore
+            ("\x1B[3;4H\x1B7\x1B[H\x1B8", (3, 2)),
+            ("\x1B[3;4H\x1B[s\x1B[H\x1B[u", (3, 2)),
+            ("\x1B[3;4H\x1B8", (3, 2)),
+            // Index, next line and reverse index
+            ("\x1B[3;4H\x1BD", (3, 3)),
+            ("\x1B[3;4H\x1BE", (0, 3)),
+            ("\x1B[3;4H\x1BM", (3, 1)),
+            ("\x1BM", (0, 0)),
+            // Attribute resets leave the cursor in place
+            ("\x1B[3;4H\x1B[m", (3, 2)),
+            ("\x1B[3;4H\x1B[1;0m", (3, 2)),
+        ];
+
+        for (data, expected) in cases {
+            assert_eq!(cursor(&parser(10, 5, data)), *expected, "{data:?}");
+        }
+    }
+
+    #[test]
+    fn edits_lines() {
+        let cases: &[(&str, &[&str])] = &[
+            // Insert, delete and erase characters
+            ("abcdef\x1B[1;3H\x1B[2@", &["ab  cdef"]),
+            ("abcdefghij\x1B[1;9H\x1B[5@", &["abcdefgh"]),
+            ("abcdef\x1B[1;3H\x1B[2P", &["abef"]),
+            ("abcdef\x1B[1;3H\x1B[99P", &["ab"]),
+            ("abcdef\x1B[1;3H\x1B[2X", &["ab  ef"]),
+            ("abcdef\x1B[1;3H\x1B[4294967295X", &["ab"]),
+            // Insert and delete lines
+            ("a\r\nb\r\nc\x1B[2;1H\x1B[L", &["a", "", "b"]),
+            ("a\r\nb\r\nc\x1B[1;1H\x1B[9L", &["", "", ""]),
+            ("a\r\nb\r\nc\x1B[1;1H\x1B[M", &["b", "c", ""]),
+            ("a\r\nb\r\nc\x1B[2;1H\x1B[4294967295M", &["a", "", ""]),
+            // Repeat the last character
+            ("a\x1B[3b", &["aaaa"]),
+            ("\x1B[3b", &[""]),
+            ("a\x1B[4294967295b", &["aaaaaaaaaa", "aaaaaaaaaa", "a"]),
+        ];
+
+        for (data, expected) in cases {
+            assert_eq!(lines(&parser(10, 3, data))[..expected.len()], **expected, "{data:?}");
+        }
+    }
+
+    #[test]
+    fn scrolls_region() {
+        let cases: &[(&str, &[&str], usize)] = &[
+            // Line feeds scroll the region, only lines leaving the top of the screen go to the scrollback
+            ("a\r\nb\r\nc\r\nd\x1B[2;3r\x1B[3;1H\n", &["a", "c", "", "d"], 0),
+            ("a\r\nb\r\nc\r\nd\x1B[1;3r\x1B[3;1H\n", &["b", "c", "", "d"], 1),
+            ("a\r\nb\r\nc\r\nd\x1B[2;3r\x1B[4;1H\n", &["a", "b", "c", "d"], 0),
+            // Reverse index at the top of the region
+            ("a\r\nb\r\nc\r\nd\x1B[2;3r\x1B[2;1H\x1BM", &["a", "", "b", "d"], 0),
+            // Scroll up and down
+            ("a\r\nb\r\nc\r\nd\x1B[2;3r\x1B[S", &["a", "c", "", "d"], 0),
+            ("a\r\nb\r\nc\r\nd\x1B[2;3r\x1B[9T", &["a", "", "", "d"], 0),
+            // Lines are inserted and deleted inside the region
+            ("a\r\nb\r\nc\r\nd\x1B[1;2r\x1B[1;1H\x1B[L", &["", "a", "c", "d"], 0),
+            ("a\r\nb\r\nc\r\nd\x1B[1;2r\x1B[3;1H\x1B[L", &["a", "b", "c", "d"], 0),
+            // Invalid and reset regions
+            ("a\r\nb\r\nc\r\nd\x1B[3;2r\x1B[4;1H\n", &["b", "c", "d", ""], 1),
+            ("a\r\nb\r\nc\r\nd\x1B[2;3r\x1B[r\x1B[4;1H\n", &["b", "c", "d", ""], 1),
+            ("a\r\nb\r\nc\r\nd\x1B[2;99r\x1B[4;1H\n", &["a", "c", "d", ""], 0),
+        ];
+
+        for (data, expected, scrollback) in cases {
+            let parser = parser(10, 4, data);
+            assert_eq!(lines(&parser), *expected, "{data:?}");
+            assert_eq!(parser.scrollback.len(), *scrollback, "{data:?}");
+        }
+
+        // Resizing resets the region
+        let mut terminal = parser(10, 4, "\x1B[2;3r\x1B[3;1H");
+        terminal.resize(10, 6);
+        terminal.parse(b"\x1B[6;1H\n");
+        assert_eq!(terminal.scrollback.len(), 1);
+    }
+
+    #[test]
+    fn switches_alternate_screen() {
+        let mut terminal = parser(10, 3, "a\r\nb\x1B[?1049hc\r\n\n\nd");
+        assert_eq!(lines(&terminal), ["", "", "d"]);
+        assert!(terminal.scrollback.is_empty());
+
+        terminal.parse(b"\x1B[?1049l");
+        assert_eq!(lines(&terminal), ["a", "b", ""]);
+        assert_eq!(cursor(&terminal), (1, 1));
+
+        // The main screen is rewrapped to the size set meanwhile
+        let mut terminal = parser(10, 3, "0123456789ab\x1B[?1049h");
+        terminal.resize(6, 3);
+        assert_eq!(lines(&terminal), ["", "", ""]);
+        terminal.parse(b"\x1B[?1049l");
+        assert_eq!(lines(&terminal), ["012345", "6789ab", ""]);
+
+        // 47 keeps the cursor moved on the alternate screen
+        let terminal = parser(10, 3, "a\x1B[?47h\x1B[3;5H\x1B[?47l");
+        assert_eq!(lines(&terminal), ["a", "", ""]);
+        assert_eq!(cursor(&terminal), (4, 2));
+    }
+
+    #[test]
+    fn skips_unsupported_sequences() {
+        let cases: &[(&str, &[&str])] = &[
+            ("a\x1B(Bb", &["ab"]),
+            ("a\x1B)0b", &["ab"]),
+            ("a\x1B#8b", &["ab"]),
+            ("a\x1B7\x1B=\x1B>b", &["ab"]),
+            ("a\x1BZb", &["ab"]),
+            ("a\x1B[2 qb", &["ab"]),
+            ("a\x1B[!pb", &["ab"]),
+            ("a\x1B(", &["a"]),
+        ];
+
+        for (data, expected) in cases {
+            assert_eq!(lines(&parser(10, 1, data)), *expected, "{data:?}");
+        }
+    }
+
+    #[test]
+    fn answers_queries() {
+        let cases: &[(&str, &[u8])] = &[
+            ("\x1B[c", DEVICE_ATTRIBUTES),
+            ("\x1B[5n", b"\x1B[0n"),
+            ("\x1B[3;4H\x1B[6n", b"\x1B[3;4R"),
+            ("0123456789\x1B[6n", b"\x1B[1;10R"),
+        ];
+
+        for (data, expected) in cases {
+            assert_eq!(parser(10, 5, data).take_responses(), *expected, "{data:?}");
+        }
+    }
+
+    #[test]
+    fn swaps_inverse_colors() {
+        let parser = parser(10, 1, "\x1B[31;7ma\x1B[27mb\x1B[7;0mc");
+        let cells = &parser.screen[0].cells;
+        assert_eq!((cells[0].foreground, cells[0].background), (Color::RGB(0, 0, 0), ansi_16_color(1, false)));
+        assert_eq!((cells[1].foreground, cells[1].background), (ansi_16_color(1, false), Color::RGB(0, 0, 0)));
+        assert_eq!((cells[2].foreground, cells[2].background), (Color::RGB(255, 255, 255), Color::RGB(0, 0, 0)));
+    }
+
+    #[test]
+    fn tracks_cursor_key_mode() {
+        let cases = [
+            ("", false),
+            ("\x1B[?1h", true),
+            ("\x1B[?1h\x1B[?1l", false),
+            ("\x1B[?25;1h", true),
+            ("\x1B[1h", false),
+            ("\x1B[?1h\x1B=\x1B>", true),
+        ];
+
+        for (data, application_cursor_keys) in cases {
+            assert_eq!(parser(10, 5, data).input_modes().application_cursor_keys, application_cursor_keys, "{data:?}");
+        }
+    }
+
+    #[test]
+    fn draws_lines() {
+        let cases: &[(&str, &[&str])] = &[
+            ("\x1B(0lqqk\x1B(B", &["┌──┐"]),
+            ("\x1B(0x  x\x1B(Bx", &["│  │x"]),
+            ("\x1B(0mqvj\r\n\x1B(Bmqvj", &["└─┴┘", "mqvj"]),
+            ("\x1B(0`afgnwtu~_A", &["◆▒°±┼┬├┤·_A"]),
+            // G1 is used between shift out and shift in
+            ("\x1B)0q\x0Eq\x0Fq", &["q─q"]),
+            ("\x0Eq\x1B)0q", &["q─"]),
+            // Saved and restored with the cursor, reset by RIS
+            ("\x1B(0\x1B7\x1B(Bq\x1B8q", &["─"]),
+            ("\x1B(0\x1Bcq", &["q"]),
+            // Repeated as drawn
+            ("\x1B(0q\x1B[3b", &["────"]),
+            // SGR resets do not change the character set
+            ("\x1B(0\x1B[0mx", &["│"]),
+        ];
+
+        for (data, expected) in cases {
+            assert_eq!(lines(&parser(12, expected.len() as u32, data)), *expected, "{data:?}");
+        }
+    }
+
+    #[test]
+    fn tracks_mouse_modes() {
+        let cases = [
+            ("", MouseTracking::None, false),
+            ("\x1B[?9h", MouseTracking::X10, false),
+            ("\x1B[?1000h", MouseTracking::Normal, false),
+            ("\x1B[?1000;1006h", MouseTracking::Normal, true),
+            ("\x1B[?1000h\x1B[?1002h", MouseTracking::ButtonEvent, false),
+            ("\x1B[?1003h\x1B[?1006h", MouseTracking::AnyEvent, true),
+            ("\x1B[?1003h\x1B[?1006h\x1B[?1003l\x1B[?1006l", MouseTracking::None, false),
+            ("\x1B[?1002h\x1B[?1000l", MouseTracking::None, false),
+            ("\x1B[?1000h\x1B[?1006h\x1Bc", MouseTracking::None, false),
+        ];
+
+        for (data, mouse_tracking, sgr_mouse) in cases {
+            let input_modes = parser(10, 5, data).input_modes();
+            assert_eq!((input_modes.mouse_tracking, input_modes.sgr_mouse), (mouse_tracking, sgr_mouse), "{data:?}");
+        }
+    }
+
+    #[test]
+    fn tracks_keyboard_modes() {
+        let cases: &[(&str, u8, u8, &[u8])] = &[
+            // Push, pop, set and query kitty keyboard flags
+            ("\x1B[>1u", 1, 0, b""),
+            ("\x1B[>1u\x1B[>9u", 9, 0, b""),
+            ("\x1B[>1u\x1B[>9u\x1B[<u", 1, 0, b""),
+            ("\x1B[>1u\x1B[>9u\x1B[<0u", 1, 0, b""),
+            ("\x1B[>1u\x1B[>9u\x1B[<2u", 0, 0, b""),
+            ("\x1B[>1u\x1B[<4294967295u", 0, 0, b""),
+            ("\x1B[<u", 0, 0, b""),
+            ("\x1B[=9u", 9, 0, b""),
+            ("\x1B[=1u\x1B[=8;2u", 9, 0, b""),
+            ("\x1B[=9u\x1B[=1;3u", 8, 0, b""),
+            ("\x1B[=9u\x1B[=1;1u", 1, 0, b""),
+            ("\x1B[?u", 0, 0, b"\x1B[?0u"),
+            ("\x1B[>9u\x1B[?u", 9, 0, b"\x1B[?9u"),
+            // xterm modifyOtherKeys
+            ("\x1B[>4;2m", 0, 2, b""),
+            ("\x1B[>4;2m\x1B[>4m", 0, 0, b""),
+            ("\x1B[>1;2m", 0, 0, b""),
+        ];
+
+        for (data, kitty_keyboard_flags, modify_other_keys, responses) in cases {
+            let mut parser = parser(10, 5, data);
+            let input_modes = parser.input_modes();
+
+            assert_eq!(input_modes.kitty_keyboard_flags, *kitty_keyboard_flags, "{data:?}");
+            assert_eq!(input_modes.modify_other_keys, *modify_other_keys, "{data:?}");
+            assert_eq!(parser.take_responses(), *responses, "{data:?}");
+        }
+    }
+
+    #[test]
+    fn drops_oldest_keyboard_flags() {
+        let mut parser = parser(10, 5, "");
+
+        for flags in 1..=KEYBOARD_FLAGS_STACK_LIMIT + 1 {
+            parser.parse(format!("\x1B[>{flags}u").as_bytes());
+        }
+        assert_eq!(parser.keyboard_flags_stack.len(), KEYBOARD_FLAGS_STACK_LIMIT);
+
+        parser.parse(format!("\x1B[<{KEYBOARD_FLAGS_STACK_LIMIT}u").as_bytes());
+        assert_eq!(parser.input_modes().kitty_keyboard_flags, 1);
+    }
+
+    #[test]
+    fn writes_at_clamped_cursor() {
+        let parser = parser(10, 5, "\x1B[4294967295;1Hx\x1B[1;4294967295Hy\x1B[4294967295Az");
+
+        assert_eq!(lines(&parser), ["         y", "z", "", "", "x"]);
+    }
+
+    #[test]
+    fn wraps_long_lines() {
+        let parser = parser(5, 3, "abcdefg");
+
+        assert_eq!(lines(&parser), ["abcde", "fg", ""]);
+        assert!(parser.screen[0].wrapped);
+        assert_eq!(cursor(&parser), (2, 1));
+    }
+
+    #[test]
+    fn reflows_on_resize() {
+        let mut parser = parser(5, 3, "abcdefg\r\nxy");
+
+        parser.resize(10, 3);
+        assert_eq!(lines(&parser), ["abcdefg", "xy", ""]);
+        assert_eq!(cursor(&parser), (2, 1));
+
+        parser.resize(3, 3);
+        assert_eq!(lines(&parser), ["def", "g", "xy"]);
+        assert_eq!(parser.scrollback.len(), 1);
+        assert_eq!(cursor(&parser), (2, 2));
+
+        parser.resize(10, 3);
+        assert_eq!(lines(&parser), ["abcdefg", "xy", ""]);
+        assert!(parser.scrollback.is_empty());
+        assert_eq!(cursor(&parser), (2, 1));
+    }
+
+    #[test]
+    fn keeps_pending_wrap_on_resize() {
+        let mut parser = parser(5, 3, "abcde");
+
+        parser.resize(5, 4);
+        assert_eq!(cursor(&parser), (5, 0));
+
+        parser.parse(b"f");
+        assert_eq!(lines(&parser), ["abcde", "f", "", ""]);
+    }
+
+    #[test]
+    fn shrinks_height_into_scrollback() {
+        let mut parser = parser(5, 4, "1\r\n2\r\n3\r\n4");
+
+        parser.resize(5, 2);
+        assert_eq!(lines(&parser), ["3", "4"]);
+        assert_eq!(parser.scrollback.len(), 2);
+        assert_eq!(cursor(&parser), (1, 1));
+    }
+}
@@ -0,0 +1,292 @@
+use appcui::prelude::Rect;
+use serde::{Deserialize, Serialize};
+
+/// Step used to grow or shrink the master area
+pub const MASTER_RATIO_STEP: f32 = 0.05;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TilingLayout {
+    Cascade,
+    Vertical,
+    Horizontal,
+    Grid,
+    /// Master windows stacked on the left, the other ones on the right
+    MasterStack,
+    /// Binary space partitioning, each window takes half of the space left by the previous one
+    Dwindle,
+    /// Every window covers the whole desktop
+    Monocle,
+}
+
+/// Tiling settings of a workspace
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Tiling {
+    /// Windows are not arranged when there is none
+    pub layout: Option<TilingLayout>,
+    /// Part of the width taken by the master windows, or by the first window in dwindle
+    pub master_ratio: f32,
+    /// Number of windows in the master area
+    pub master_count: usize,
+}
+
+impl Default for Tiling {
+    fn default() -> Self {
+        Self {
+            layout: None,
+            master_ratio: 0.5,
+            master_count: 1,
+        }
+    }
+}
+
+impl Tiling {
+    pub fn resize_master(&mut self, delta: f32) {
+        self.master_ratio = (self.master_ratio + delta).clamp(0.1, 0.9);
+    }
+
+    pub fn change_master_count(&mut self, delta: isize) {
+        self.master_count = self.master_count.saturating_add_signed(delta).max(1);
+    }
+
+    /// Positions of `count` tiled windows in `area`, in tiling order
+    pub fn arrange(&self, area: Rect, count: usize) -> Vec<Rect> {
+        let Some(layout) = self.layout else {
+            return Vec::new();
+        };
+
+        if count == 0 {
+            return Vec::new();
+        }
+
+        match layout {
+            TilingLayout::Cascade => (0..count)
+                .map(|index| {
+                    let left = (area.left() + index as i32).min(area.right());
+                    let top = (area.top() + index as i32).min(area.bottom());
+                    Rect::new(left, top, area.right(), area.bottom())
+                })
+                .collect(),
+            TilingLayout::Vertical => split(area, count, 1, count),
+            TilingLayout::Horizontal => split(area, 1, count, count),
+            TilingLayout::Grid => {
+                let mut columns = (count as f32).sqrt() as usize;
+                if columns * columns < count {
+                    columns += 1;
+                }
+
+                split(area, columns, count.div_ceil(columns), count)
+            }
+            TilingLayout::MasterStack => {
+                let masters = self.master_count.min(count);
+
+                if masters == count {
+                    return split(area, 1, count, count);
+                }
+
+                let (master_area, stack_area) = split_columns_at(area, self.master_ratio);
+                let mut positions = split(master_area, 1, masters, masters);
+                positions.extend(split(stack_area, 1, count - masters, count - masters));
+                positions
+            }
+            TilingLayout::Dwindle => {
+                let mut positions = Vec::new();
+                let mut remaining = area;
+
+                for index in 0..count - 1 {
+                    let ratio = match index {
+                        0 => self.master_ratio,
+                        _ => 0.5,
+                    };
+
+                    // Alternate between side by side and stacked splits
+                    let (tile, rest) = match index % 2 {
+                        0 => split_columns_at(remaining, ratio),
+                        _ => split_rows_at(remaining, ratio),
+                    };
+
+                    positions.push(tile);
+                    remaining = rest;
+                }
+
+                positions.push(remaining);
+                positions
+            }
+            TilingLayout::Monocle => vec![area; count],
+        }
+    }
+}
+
+/// Split the area into a grid filled row by row, the last window of a row or column takes the remaining space.
+/// Tiles that do not fit in an area smaller than the grid are stacked on its last column or row
+fn split(area: Rect, columns: usize, rows: usize, count: usize) -> Vec<Rect> {
+    let width = (area.width() as i32 / columns as i32).max(1);
+    let height = (area.height() as i32 / rows as i32).max(1);
+
+    (0..count)
+        .map(|index| {
+            let column = (index % columns) as i32;
+            let row = (index / columns) as i32;
+            let left = (area.left() + column * width).min(area.right());
+            let top = (area.top() + row * height).min(area.bottom());
+
+            let right = match column + 1 == columns as i32 || index + 1 == count {
+                true => area.right(),
+                false => (left + width - 1).min(area.right()),
+            };
+            let bottom = match row + 1 == rows as i32 {
+                true => area.bottom(),
+                false => (top + height - 1).min(area.bottom()),
+            };
+
+            Rect::new(left, top, right, bottom)
+        })
+        .collect()
+}
+
+/// Left and right parts of the area, the left one taking `ratio` of the width.
+/// Both parts are the whole area when it is a single column wide
+fn split_columns_at(area: Rect, ratio: f32) -> (Rect, Rect) {
+    if area.width() < 2 {
+        return (area, area);
+    }
+
+    let width = ((area.width() as f32 * ratio) as i32).clamp(1, area.width() as i32 - 1);
+
+    (
+        Rect::new(area.left(), area.top(), area.left() + width - 1, area.bottom()),
+        Rect::new(area.left() + width, area.top(), area.right(), area.bottom()),
+    )
+}
+
+/// Top and bottom parts of the area, the top one taking `ratio` of the height.
+/// Both parts are the whole area when it is a single row high
+fn split_rows_at(area: Rect, ratio: f32) -> (Rect, Rect) {
+    if area.height() < 2 {
+        return (area, area);
+    }
+
+    let height = ((area.height() as f32 * ratio) as i32).clamp(1, area.height() as i32 - 1);
+
+    (
+        Rect::new(area.left(), area.top(), area.right(), area.top() + height - 1),
+        Rect::new(area.left(), area.top() + height, area.right(), area.bottom()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LAYOUTS: [TilingLayout; 7] = [
+        TilingLayout::Cascade,
+        TilingLayout::Vertical,
+        TilingLayout::Horizontal,
+        TilingLayout::Grid,
+        TilingLayout::MasterStack,
+        TilingLayout::Dwindle,
+        TilingLayout::Monocle,
+    ];
+
+    fn tiling(layout: TilingLayout) -> Tiling {
+        Tiling {
+            layout: Some(layout),
+            master_count: 2,
+            ..Tiling::default()
+        }
+    }
+
+    fn contains(area: Rect, tile: Rect) -> bool {
+        tile.left() >= area.left() && tile.right() <= area.right() && tile.top() >= area.top() && tile.bottom() <= area.bottom()
+    }
+
+    fn overlap(a: Rect, b: Rect) -> bool {
+        a.left() <= b.right() && b.left() <= a.right() && a.top() <= b.bottom() && b.top() <= a.bottom()
+    }
+
+    #[test]
+    fn keeps_tiles_in_area() {
+        let areas = [
+            Rect::new(0, 1, 0, 1),
+            Rect::new(0, 1, 1, 1),
+            Rect::new(0, 1, 0, 2),
+            Rect::new(0, 1, 2, 2),
+            Rect::new(5, 3, 84, 26),
+        ];
+
+        for layout in LAYOUTS {
+            for area in areas {
+                for count in 1..=9 {
+                    let tiles = tiling(layout).arrange(area, count);
+
+                    assert_eq!(tiles.len(), count, "{layout:?} {area:?} {count}");
+                    for tile in tiles {
+                        assert!(contains(area, tile), "{layout:?} {area:?} {count}: {tile:?}");
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn covers_large_areas() {
+        let area = Rect::new(5, 3, 84, 26);
+
+        for layout in [TilingLayout::Vertical, TilingLayout::Horizontal, TilingLayout::Grid, TilingLayout::MasterStack, TilingLayout::Dwindle] {
+            for count in 1..=9 {
+                let tiles = tiling(layout).arrange(area, count);
+                let covered: u32 = tiles.iter().map(|tile| tile.width() * tile.height()).sum();
+
+                assert_eq!(covered, area.width() * area.height(), "{layout:?} {count}");
+                for (index, tile) in tiles.iter().enumerate() {
+                    for other in &tiles[index + 1..] {
+                        assert!(!overlap(*tile, *other), "{layout:?} {count}: {tile:?} {other:?}");
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn arranges_nothing_without_layout() {
+        assert!(Tiling::default().arrange(Rect::new(0, 0, 79, 23), 3).is_empty());
+        assert!(tiling(TilingLayout::Grid).arrange(Rect::new(0, 0, 79, 23), 0).is_empty());
+    }
+
+    #[test]
+    fn places_master_windows() {
+        let tiles = tiling(TilingLayout::MasterStack).arrange(Rect::new(0, 0, 79, 23), 3);
+
+        assert_eq!(tiles, [Rect::new(0, 0, 39, 11), Rect::new(0, 12, 39, 23), Rect::new(40, 0, 79, 23)]);
+    }
+
+    #[test]
+    fn halves_dwindle_tiles() {
+        let tiles = tiling(TilingLayout::Dwindle).arrange(Rect::new(0, 0, 79, 23), 4);
+
+        assert_eq!(tiles, [Rect::new(0, 0, 39, 23), Rect::new(40, 0, 79, 11), Rect::new(40, 12, 59, 23), Rect::new(60, 12, 79, 23)]);
+    }
+
+    #[test]
+    fn stacks_monocle_windows() {
+        let area = Rect::new(0, 1, 79, 23);
+
+        assert_eq!(tiling(TilingLayout::Monocle).arrange(area, 3), [area; 3]);
+    }
+
+    #[test]
+    fn bounds_master_settings() {
+        let mut tiling = tiling(TilingLayout::MasterStack);
+
+        tiling.resize_master(1.0);
+        assert_eq!(tiling.master_ratio, 0.9);
+        tiling.resize_master(-1.0);
+        assert_eq!(tiling.master_ratio, 0.1);
+
+        tiling.change_master_count(-5);
+        assert_eq!(tiling.master_count, 1);
+        tiling.change_master_count(2);
+        assert_eq!(tiling.master_count, 3);
+    }
+}
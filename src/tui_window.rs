@@ -1,39 +1,127 @@
-use crate::terminal_emulation::TerminalParser;
+use crate::terminal_emulation::{InputModes, TerminalParser};
 use anyhow::anyhow;
-use appcui::dialogs::{Location, OpenFileDialogFlags, SelectFolderDialogFlags};
-use appcui::graphics::{CharAttribute, CharFlags, Character, Color, Size, Surface};
+use appcui::graphics::{CharFlags, Character, Color, Size, Surface};
 use appcui::prelude::window::Flags;
-use appcui::prelude::{canvas, Alignment, Canvas, EventProcessStatus, Handle, LayoutBuilder, OnResize, TimerEvents, Window};
+use appcui::prelude::{canvas, toolbar, Alignment, Canvas, EventProcessStatus, Handle, LayoutBuilder, TimerEvents, ToolBarEvents, Window, WindowEvents};
 use async_channel::{Receiver, Sender};
+use std::env;
 use std::ffi::OsStr;
 use std::path::Path;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use virtual_terminal::{Command, Input, Output};
-use crate::shortcut::{BackgroundColor, TerminalOptions, WindowOptions, WindowSize};
+use crate::keybindings::{DesktopAction, KeyBindings};
+use crate::panes::{neighbour, PaneDirection, PaneTree, SplitDirection};
+use crate::placeholders::replace_placeholders;
+use crate::session::{PaneState, TabState};
+use crate::shortcut::{BackgroundColor, LaunchOptions, PaneOptions, TerminalOptions, WindowOptions, WindowSize};
+use crate::utils::{expand_vars, find_program};
+use crate::tab_bar::TabBar;
 
-#[CustomControl(overwrite = OnKeyPressed)]
+#[CustomControl(overwrite = OnKeyPressed + OnMouseEvent)]
 pub struct CustomKeyboardControl {
-    pub should_exit: bool,
+    pub input_modes: InputModes,
+    /// Cell the mouse was seen on last, where the wheel is reported
+    pub mouse_position: Point,
+    pub key_bindings: KeyBindings,
+    /// Prefix key and character typed, waiting for the rest of the sequence
+    pub pending_prefix: Option<(Key, char)>,
+    pub action_tx: Sender<DesktopAction>,
     pub tx: Sender<Input>,
     pub rx: Receiver<Output>,
 }
 
-#[Window(events = TimerEvents)]
-pub struct TuiWindow {
+/// Time to wait after the last size change before resizing the terminal, so dragging a border does not flood the child with SIGWINCH
+const RESIZE_DEBOUNCE: Duration = Duration::from_millis(100);
+
+/// Time a window must stay still after being moved before it is grouped with the window it was dropped on
+const DROP_DELAY: Duration = Duration::from_millis(500);
+
+/// The emulator implements the scrolling regions, line and character editing, alternate screen, line drawing characters
+/// and mouse tracking of xterm, with 256 and 24-bit colors
+const TERM: &str = "xterm-256color";
+const COLORTERM: &str = "truecolor";
+
+/// Terminal shown in a part of a window, with its own PTY
+pub struct Pane {
     pub canvas: Handle<Canvas>,
+    pub keyboard_control: Handle<CustomKeyboardControl>,
+    pub terminal_parser: TerminalParser,
+    /// Size of the PTY
+    pub size: Size,
+    /// Process started in the pane, once it is known
+    pub pid: Option<u32>,
+    /// Program and arguments before the placeholders were replaced, with the working directory and environment
+    pub command: String,
+    pub args: Vec<String>,
+    pub launch: LaunchOptions,
+}
+
+/// Splits shown in a tab of a window
+#[derive(Clone, Debug)]
+pub struct Tab {
+    /// Label used until the application sets a title
+    pub name: String,
+    pub pane_tree: PaneTree,
+    pub active_pane: usize,
+    /// The active pane covers the whole window, the other ones are hidden
+    pub zoomed: bool,
+}
+
+/// Running terminal of a pane, moved between windows when they are grouped
+pub struct PaneSession {
     pub terminal_parser: TerminalParser,
-    pub custom_keyboard_control: Handle<CustomKeyboardControl>,
+    pub input_modes: InputModes,
+    pub tx: Sender<Input>,
+    pub rx: Receiver<Output>,
+    pub size: Size,
+    pub pid: Option<u32>,
+    pub command: String,
+    pub args: Vec<String>,
+    pub launch: LaunchOptions,
+}
+
+/// A tab and the terminals of its panes, indexed by their id in the tab
+pub struct TabSession {
+    pub tab: Tab,
+    pub panes: Vec<(usize, PaneSession)>,
+}
+
+#[Window(events = TimerEvents + WindowEvents + ToolBarEvents)]
+pub struct TuiWindow {
+    pub minimize_button: Handle<toolbar::Button>,
+    /// Geometry before the window was maximized
+    pub restore_rect: Option<Rect>,
+    /// Panes of every tab indexed by id, `None` once closed
+    pub panes: Vec<Option<Pane>>,
+    pub tabs: Vec<Tab>,
+    pub active_tab: usize,
+    /// Shown above the panes when there are several tabs
+    pub tab_bar: Handle<TabBar>,
+    pub padding: (i32, i32),
     pub horizontal_adjustment: u32,
-    pub vertical_adjustment: u32
+    pub vertical_adjustment: u32,
+    pub pending_resize: Option<Instant>,
+    /// Geometry given by the desktop, its layout change is not a move made by the user
+    pub expected_rect: Option<Rect>,
+    /// Last time the user moved the window
+    pub moved_at: Option<Instant>,
+    pub default_background_color: Color,
+    pub key_bindings: KeyBindings,
+    pub action_tx: Sender<DesktopAction>,
 }
 
 impl TuiWindow {
+    #[allow(clippy::too_many_arguments)]
     pub fn new<S, I>(
         app_name: &str,
         program: S,
         args: I,
+        launch: LaunchOptions,
+        panes: Vec<PaneOptions>,
         window_options: WindowOptions,
         terminal_options: TerminalOptions,
+        key_bindings: KeyBindings,
+        action_tx: Sender<DesktopAction>,
     ) -> anyhow::Result<Self> where S: AsRef<OsStr>, I: IntoIterator<Item = S> {
         let window_size = window_options.size
             .unwrap_or(WindowSize {
@@ -41,23 +129,9 @@ impl TuiWindow {
                 height: 25,
             });
 
-        let mut x = 0;
-        let mut y = 0;
-        let mut horizontal_adjustment: i32 = 2;
-        let mut vertical_adjustment: i32 = 2;
-
-        if let Some(padding) = terminal_options.padding {
-            x = padding.0;
-            y = padding.1;
-
-            horizontal_adjustment += padding.0;
-            vertical_adjustment += padding.1;
-        }
-
-        let inner_size = Size {
-            width: window_size.width.saturating_sub(horizontal_adjustment as u32),
-            height: window_size.height.saturating_sub(vertical_adjustment as u32),
-        };
+        let padding = terminal_options.padding.unwrap_or((0, 0));
+        let horizontal_adjustment = 2 + padding.0;
+        let vertical_adjustment = 2 + padding.1;
 
         let mut window_flags = Flags::None;
 
@@ -83,31 +157,6 @@ impl TuiWindow {
             window_flags
         );
 
-        let mut modified_program = replace_file_path(program.as_ref().to_str().unwrap().to_string())?;
-        modified_program = replace_folder_path(modified_program)?;
-        let mut modified_args: Vec<String> = Vec::new();
-
-        for arg in args {
-            let mut modified_arg = replace_file_path(arg.as_ref().to_str().unwrap().to_string())?;
-            modified_arg = replace_folder_path(modified_arg)?;
-            modified_args.push(modified_arg);
-        }
-
-        let cmd = Command::new(modified_program)
-            .args(modified_args)
-            .terminal_size((
-                inner_size.width as usize,
-                inner_size.height as usize
-            ));
-
-        let rx = cmd.out_rx();
-        let tx = cmd.in_tx();
-
-        tx.send_blocking(Input::Resize((
-            inner_size.width as usize,
-            inner_size.height as usize
-        )))?;
-
         let default_background_color = match terminal_options.background_color {
             None => Color::RGB(0, 0, 0),
             Some(BackgroundColor { r, g, b }) => Color::RGB(r, g, b),
@@ -115,125 +164,778 @@ impl TuiWindow {
 
         let mut tui_win = Self {
             base: win,
-            canvas: Handle::None,
-            custom_keyboard_control: Handle::None,
-            terminal_parser: TerminalParser::new(
-                window_size.width,
-                window_size.height,
-                default_background_color
-            ),
-            horizontal_adjustment: horizontal_adjustment  as u32,
+            minimize_button: Handle::None,
+            restore_rect: None,
+            panes: Vec::new(),
+            tabs: vec![Tab {
+                name: app_name.to_string(),
+                pane_tree: PaneTree::Pane(0),
+                active_pane: 0,
+                zoomed: false,
+            }],
+            active_tab: 0,
+            tab_bar: Handle::None,
+            padding,
+            horizontal_adjustment: horizontal_adjustment as u32,
             vertical_adjustment: vertical_adjustment as u32,
+            pending_resize: None,
+            expected_rect: None,
+            moved_at: None,
+            default_background_color,
+            key_bindings,
+            action_tx,
         };
 
-        tui_win.canvas = tui_win.add(Canvas::new(
-            Size::new(inner_size.width, inner_size.height),
+        tui_win.tab_bar = tui_win.add(TabBar::new(
             LayoutBuilder::new()
-                .width(inner_size.width)
-                .height(inner_size.height)
-                .x(x)
-                .y(y)
-                .build(),
-            canvas::Flags::None
+                .x(padding.0)
+                .y(padding.1)
+                .width(1)
+                .height(1)
+                .build()
         ));
 
-        let c = tui_win.canvas;
-        if let Some(cv) = tui_win.control_mut(c) {
-            let surface = cv.drawing_surface_mut();
-            surface.fill_rect(
-                Rect::new(0, 0, inner_size.width as i32, inner_size.height as i32),
-                Character::new(' ', Color::Transparent, default_background_color, CharFlags::None)
-            );
-            surface.write_string(0, 0, "Loading...", CharAttribute::default(), false);
+        let program = program.as_ref().to_str().unwrap().to_string();
+        let args: Vec<String> = args
+            .into_iter()
+            .map(|arg| arg.as_ref().to_str().unwrap().to_string())
+            .collect();
+
+        let area = Rect::with_size(
+            padding.0,
+            padding.1,
+            window_size.width.saturating_sub(horizontal_adjustment as u32) as u16,
+            window_size.height.saturating_sub(vertical_adjustment as u32) as u16
+        );
+        tui_win.open_pane(0, area, &program, &args, &launch)?;
+
+        for pane in panes {
+            // The window has no size until it is added to the desktop
+            tui_win.split_pane(area, pane.split, pane.ratio.unwrap_or(0.5), &pane.command, &pane.args, &launch)?;
         }
 
+        let group = tui_win.toolbar().create_group(toolbar::GroupPosition::TopRight);
+        tui_win.minimize_button = tui_win.toolbar().add(group, toolbar::Button::new("_"));
+
         let timer = match tui_win.timer() {
             Some(t) => t,
             None => return Err(anyhow!("Failed to get timer"))
         };
         timer.start(Duration::from_millis(25));
 
-        tui_win.custom_keyboard_control = tui_win.add(CustomKeyboardControl {
-            should_exit: false,
-            base: ControlBase::new(Layout::fill(), true),
+        Ok(tui_win)
+    }
+
+    fn tab(&self) -> &Tab {
+        &self.tabs[self.active_tab]
+    }
+
+    fn tab_mut(&mut self) -> &mut Tab {
+        &mut self.tabs[self.active_tab]
+    }
+
+    /// Start the program in a new pane placed at `rect`
+    fn open_pane(&mut self, id: usize, rect: Rect, program: &str, args: &[String], launch: &LaunchOptions) -> anyhow::Result<()> {
+        let (modified_program, modified_args) = replace_placeholders(program, args, launch.dialog_dir.as_deref(), launch.shell)?;
+
+        let size = rect.size();
+
+        let cmd = launch_command(&modified_program, &modified_args, launch)?
+            .terminal_size((
+                size.width as usize,
+                size.height as usize
+            ));
+
+        let rx = cmd.out_rx();
+        let tx = cmd.in_tx();
+
+        tx.send_blocking(Input::Resize((
+            size.width as usize,
+            size.height as usize
+        )))?;
+
+        self.attach_pane(id, rect, PaneSession {
+            terminal_parser: TerminalParser::new(size.width, size.height, self.default_background_color),
+            input_modes: InputModes::default(),
             tx,
             rx,
+            size,
+            pid: None,
+            command: program.to_string(),
+            args: args.to_vec(),
+            launch: launch.clone(),
         });
 
         tokio::spawn(cmd.run());
 
-        let c = tui_win.canvas;
-        if let Some(cv) = tui_win.control_mut(c) {
-            let surface = cv.drawing_surface_mut();
-            surface.clear(Character::new(' ', Color::Transparent, Color::Transparent, CharFlags::None));
+        self.focus_pane(id);
+
+        Ok(())
+    }
+
+    /// Show the terminal in a new pane placed at `rect`
+    fn attach_pane(&mut self, id: usize, rect: Rect, session: PaneSession) {
+        let size = rect.size();
+
+        let canvas = self.add(Canvas::new(
+            size,
+            LayoutBuilder::new()
+                .width(size.width)
+                .height(size.height)
+                .x(rect.left())
+                .y(rect.top())
+                .build(),
+            canvas::Flags::None
+        ));
+
+        let default_background_color = self.default_background_color;
+        if let Some(cv) = self.control_mut(canvas) {
+            cv.drawing_surface_mut().clear(Character::new(' ', Color::Transparent, default_background_color, CharFlags::None));
         }
 
-        Ok(tui_win)
+        let key_bindings = self.key_bindings.clone();
+        let action_tx = self.action_tx.clone();
+
+        let keyboard_control = self.add(CustomKeyboardControl {
+            input_modes: session.input_modes,
+            mouse_position: Point::ORIGIN,
+            key_bindings,
+            pending_prefix: None,
+            action_tx,
+            base: ControlBase::new(
+                LayoutBuilder::new()
+                    .width(size.width)
+                    .height(size.height)
+                    .x(rect.left())
+                    .y(rect.top())
+                    .build(),
+                true
+            ),
+            tx: session.tx,
+            rx: session.rx,
+        });
+
+        if self.panes.len() <= id {
+            self.panes.resize_with(id + 1, || None);
+        }
+
+        self.panes[id] = Some(Pane {
+            canvas,
+            keyboard_control,
+            terminal_parser: session.terminal_parser,
+            size: session.size,
+            pid: session.pid,
+            command: session.command,
+            args: session.args,
+            launch: session.launch,
+        });
+    }
+
+    /// Share the space of the active pane with a new pane running the program
+    pub fn split_active_pane(&mut self, direction: SplitDirection, ratio: f32, program: &str, args: &[String], launch: &LaunchOptions) -> anyhow::Result<()> {
+        self.split_pane(self.panes_area(), direction, ratio, program, args, launch)
+    }
+
+    fn split_pane(&mut self, area: Rect, direction: SplitDirection, ratio: f32, program: &str, args: &[String], launch: &LaunchOptions) -> anyhow::Result<()> {
+        let id = self.panes.len();
+        let mut pane_tree = self.tab().pane_tree.clone();
+        pane_tree.split(self.tab().active_pane, id, direction, ratio);
+
+        let rect = pane_tree
+            .layout(area)
+            .into_iter()
+            .find(|(pane, _)| *pane == id)
+            .map(|(_, rect)| rect)
+            .ok_or(anyhow!("Failed to split pane {}", self.tab().active_pane))?;
+
+        self.open_pane(id, rect, program, args, launch)?;
+
+        let tab = self.tab_mut();
+        tab.pane_tree = pane_tree;
+        tab.zoomed = false;
+        self.layout_panes_in(area);
+
+        Ok(())
+    }
+
+    /// Stop the application of the pane, the tab is closed with its last pane and the window with its last tab
+    pub fn close_pane(&mut self, id: usize) {
+        let Some(pane) = self.panes.get_mut(id).and_then(Option::take) else {
+            return;
+        };
+
+        if let Some(control) = self.control_mut(pane.keyboard_control) {
+            control.tx.send_blocking(Input::Terminate).ok();
+            control.tx.close();
+            control.rx.close();
+        }
+
+        self.hide_pane(&pane);
+
+        let Some(tab_index) = self.tabs.iter().position(|tab| tab.pane_tree.contains(id)) else {
+            return;
+        };
+
+        let tab = &mut self.tabs[tab_index];
+        tab.zoomed = false;
+
+        if !tab.pane_tree.remove(id) {
+            self.tabs.remove(tab_index);
+
+            if self.tabs.is_empty() {
+                self.close();
+                return;
+            }
+
+            if self.active_tab >= tab_index && self.active_tab > 0 {
+                self.active_tab -= 1;
+            }
+
+            self.select_tab(self.active_tab);
+            return;
+        }
+
+        if tab.active_pane == id {
+            let first_pane = tab.pane_tree.layout(Rect::with_size(0, 0, 1, 1))[0].0;
+            tab.active_pane = first_pane;
+
+            if tab_index == self.active_tab {
+                self.focus_pane(first_pane);
+            }
+        }
+
+        self.layout_panes();
+    }
+
+    /// Controls cannot be removed from a window
+    fn hide_pane(&mut self, pane: &Pane) {
+        if let Some(control) = self.control_mut(pane.keyboard_control) {
+            control.set_visible(false);
+        }
+
+        if let Some(cv) = self.control_mut(pane.canvas) {
+            cv.set_visible(false);
+        }
+    }
+
+    pub fn close_active_pane(&mut self) {
+        self.close_pane(self.tab().active_pane);
+    }
+
+    /// Focus the closest pane in the direction
+    pub fn focus_neighbour_pane(&mut self, direction: PaneDirection) {
+        let layout = self.tab().pane_tree.layout(self.panes_area());
+
+        if let Some(id) = neighbour(&layout, self.tab().active_pane, direction) {
+            if self.tab().zoomed {
+                self.tab_mut().zoomed = false;
+                self.layout_panes();
+            }
+
+            self.focus_pane(id);
+        }
+    }
+
+    fn focus_pane(&mut self, id: usize) {
+        let Some(pane) = self.panes.get(id).and_then(Option::as_ref) else {
+            return;
+        };
+
+        let keyboard_control = pane.keyboard_control;
+        self.tab_mut().active_pane = id;
+        self.request_focus_for_control(keyboard_control);
+    }
+
+    /// Grow the active pane, a negative delta shrinks it
+    pub fn resize_active_pane(&mut self, delta: f32) {
+        let tab = self.tab_mut();
+
+        if tab.pane_tree.resize(tab.active_pane, delta) {
+            self.layout_panes();
+        }
+    }
+
+    /// Let the active pane cover the whole window, or show every pane again
+    pub fn toggle_zoom(&mut self) {
+        let tab = self.tab_mut();
+
+        if matches!(tab.pane_tree, PaneTree::Pane(_)) && !tab.zoomed {
+            return;
+        }
+
+        tab.zoomed = !tab.zoomed;
+        self.layout_panes();
+    }
+
+    /// Show the tab `offset` positions after the active one
+    pub fn cycle_tab(&mut self, offset: isize) {
+        let tab = (self.active_tab as isize + offset).rem_euclid(self.tabs.len() as isize) as usize;
+        self.select_tab(tab);
+    }
+
+    pub fn select_tab(&mut self, tab: usize) {
+        if tab >= self.tabs.len() {
+            return;
+        }
+
+        self.active_tab = tab;
+        self.layout_panes();
+        self.focus_pane(self.tab().active_pane);
+    }
+
+    /// Take every tab out of the window and close it without stopping the applications
+    pub fn take_tabs(&mut self) -> Vec<TabSession> {
+        let mut sessions = Vec::new();
+
+        for tab in std::mem::take(&mut self.tabs) {
+            let mut panes = Vec::new();
+
+            for (id, _) in tab.pane_tree.layout(Rect::with_size(0, 0, 1, 1)) {
+                let Some(pane) = self.panes.get_mut(id).and_then(Option::take) else {
+                    continue;
+                };
+
+                let Some(control) = self.control(pane.keyboard_control) else {
+                    continue;
+                };
+
+                let (tx, rx, input_modes) = (control.tx.clone(), control.rx.clone(), control.input_modes);
+                self.hide_pane(&pane);
+
+                panes.push((id, PaneSession {
+                    terminal_parser: pane.terminal_parser,
+                    input_modes,
+                    tx,
+                    rx,
+                    size: pane.size,
+                    pid: pane.pid,
+                    command: pane.command,
+                    args: pane.args,
+                    launch: pane.launch,
+                }));
+            }
+
+            sessions.push(TabSession {
+                tab,
+                panes,
+            });
+        }
+
+        self.close();
+        sessions
+    }
+
+    /// Add tabs taken from another window, the last one is shown
+    pub fn add_tabs(&mut self, sessions: Vec<TabSession>) {
+        let area = self.panes_area();
+
+        for session in sessions {
+            let mut ids = Vec::new();
+
+            for (old_id, pane_session) in session.panes {
+                let id = self.panes.len();
+                self.attach_pane(id, area, pane_session);
+                ids.push((old_id, id));
+            }
+
+            let new_id = |old_id: usize| ids.iter().find(|(old, _)| *old == old_id).map_or(old_id, |(_, new)| *new);
+
+            self.tabs.push(Tab {
+                pane_tree: session.tab.pane_tree.renumber(&new_id),
+                active_pane: new_id(session.tab.active_pane),
+                ..session.tab
+            });
+        }
+
+        self.select_tab(self.tabs.len() - 1);
+    }
+
+    /// Tabs and pane commands to save in the session
+    pub fn tab_states(&self) -> Vec<TabState> {
+        self.tabs
+            .iter()
+            .map(|tab| TabState {
+                name: tab.name.clone(),
+                pane_tree: tab.pane_tree.clone(),
+                active_pane: tab.active_pane,
+                panes: tab.pane_tree
+                    .layout(Rect::with_size(0, 0, 1, 1))
+                    .into_iter()
+                    .filter_map(|(id, _)| {
+                        let pane = self.panes.get(id)?.as_ref()?;
+
+                        Some(PaneState {
+                            id,
+                            command: pane.command.clone(),
+                            args: pane.args.clone(),
+                            launch: pane.launch.clone(),
+                        })
+                    })
+                    .collect(),
+            })
+            .collect()
+    }
+
+    /// Reopen the panes of saved tabs, the window runs the first pane of the first tab already.
+    /// Panes that fail to start are left out of their tab, and reported
+    pub fn restore_tabs(&mut self, tabs: Vec<TabState>, active_tab: usize) -> Vec<String> {
+        let area = self.panes_area();
+        let mut problems = Vec::new();
+
+        for (index, state) in tabs.into_iter().enumerate() {
+            let mut ids = Vec::new();
+            let mut panes = state.panes.into_iter();
+
+            if index == 0 && let Some(first_pane) = panes.next() {
+                ids.push((first_pane.id, 0));
+            }
+
+            for pane in panes {
+                if !state.pane_tree.contains(pane.id) {
+                    continue;
+                }
+
+                let id = self.panes.len();
+
+                match self.open_pane(id, area, &pane.command, &pane.args, &pane.launch) {
+                    Ok(()) => ids.push((pane.id, id)),
+                    Err(error) => problems.push(format!("{}: {error}", pane.command)),
+                }
+            }
+
+            // Leave out the panes that were not started
+            let mut pane_tree = state.pane_tree;
+            for (old_id, _) in pane_tree.layout(Rect::with_size(0, 0, 1, 1)) {
+                if !ids.iter().any(|(old, _)| *old == old_id) {
+                    pane_tree.remove(old_id);
+                }
+            }
+
+            let Some(&(_, first_id)) = ids.first() else {
+                continue;
+            };
+
+            let new_id = |old_id: usize| ids.iter().find(|(old, _)| *old == old_id).map(|(_, new)| *new);
+            let tab = Tab {
+                name: state.name,
+                pane_tree: pane_tree.renumber(&|old_id| new_id(old_id).unwrap_or(first_id)),
+                active_pane: new_id(state.active_pane).unwrap_or(first_id),
+                zoomed: false,
+            };
+
+            match index {
+                0 => self.tabs[0] = tab,
+                _ => self.tabs.push(tab),
+            }
+        }
+
+        self.select_tab(active_tab.min(self.tabs.len() - 1));
+        problems
+    }
+
+    /// Parser of the active pane
+    pub fn active_parser(&self) -> Option<&TerminalParser> {
+        let tab = self.tabs.get(self.active_tab)?;
+
+        self.panes
+            .get(tab.active_pane)
+            .and_then(Option::as_ref)
+            .map(|pane| &pane.terminal_parser)
+    }
+
+    /// Processes of the panes of every tab
+    pub fn pids(&self) -> Vec<u32> {
+        self.panes
+            .iter()
+            .flatten()
+            .filter_map(|pane| pane.pid)
+            .collect()
+    }
+
+    /// Send raw input to the application of the active pane
+    pub fn send_input(&self, data: Vec<u8>) {
+        let Some(pane) = self.panes.get(self.tab().active_pane).and_then(Option::as_ref) else {
+            return;
+        };
+
+        if let Some(control) = self.control(pane.keyboard_control) {
+            control.tx.send_blocking(Input::Data(data)).ok();
+        }
+    }
+
+    /// Used by the panes to find the prefix sequences
+    pub fn set_key_bindings(&mut self, key_bindings: KeyBindings) {
+        let keyboard_controls: Vec<Handle<CustomKeyboardControl>> = self.panes.iter().flatten().map(|pane| pane.keyboard_control).collect();
+
+        for keyboard_control in keyboard_controls {
+            if let Some(control) = self.control_mut(keyboard_control) {
+                control.key_bindings = key_bindings.clone();
+            }
+        }
+
+        self.key_bindings = key_bindings;
+    }
+
+    /// Keyboard modes requested by the application of the active pane
+    pub fn input_modes(&self) -> InputModes {
+        self.active_parser().map(TerminalParser::input_modes).unwrap_or_default()
     }
 
     pub fn close_command(&mut self) {
-        let custom_keyboard_control = self.custom_keyboard_control;
-        let control = self.control_mut(custom_keyboard_control).unwrap();
-        control.tx.send_blocking(Input::Terminate).ok();
-        control.tx.close();
-        control.rx.close();
+        for pane in self.panes.iter().flatten() {
+            if let Some(control) = self.control(pane.keyboard_control) {
+                control.tx.send_blocking(Input::Terminate).ok();
+                control.tx.close();
+                control.rx.close();
+            }
+        }
+
         self.close();
     }
-}
 
-impl TimerEvents for TuiWindow {
-    fn on_update(&mut self, _: u64) -> EventProcessStatus {
-        let (should_close, (rx_clone, tx_clone)) = {
-            let ckc = self.control(self.custom_keyboard_control).unwrap();
+    /// Client area of the window without the padding
+    fn inner_area(&self) -> Rect {
+        let size = self.size();
+
+        Rect::with_size(
+            self.padding.0,
+            self.padding.1,
+            size.width.saturating_sub(self.horizontal_adjustment) as u16,
+            size.height.saturating_sub(self.vertical_adjustment) as u16
+        )
+    }
+
+    /// Inner area without the tab bar
+    fn panes_area(&self) -> Rect {
+        let area = self.inner_area();
+
+        match self.tabs.len() > 1 {
+            true => Rect::new(area.left(), (area.top() + 1).min(area.bottom()), area.right(), area.bottom()),
+            false => area,
+        }
+    }
 
-            (ckc.should_exit, (ckc.rx.clone(), ckc.tx.clone()))
+    /// Fit the canvases, the parsers and the PTYs of the panes of the active tab to the current window size, hide the other ones
+    pub fn layout_panes(&mut self) {
+        self.layout_panes_in(self.panes_area());
+    }
+
+    fn layout_panes_in(&mut self, area: Rect) {
+        let inner_area = self.inner_area();
+        let show_tab_bar = self.tabs.len() > 1;
+        let tab_bar = self.tab_bar;
+
+        if let Some(bar) = self.control_mut(tab_bar) {
+            bar.set_visible(show_tab_bar);
+            bar.set_position(inner_area.left(), inner_area.top());
+            bar.set_size(inner_area.width() as u16, 1);
+        }
+
+        self.update_tab_bar();
+
+        let tab = self.tab();
+        let positions = match tab.zoomed {
+            true => vec![(tab.active_pane, area)],
+            false => tab.pane_tree.layout(area),
         };
 
-        if should_close {
-            self.close_command();
-            return EventProcessStatus::Processed;
+        for id in 0..self.panes.len() {
+            let rect = positions
+                .iter()
+                .find(|(pane, _)| *pane == id)
+                .map(|(_, rect)| *rect);
+
+            self.place_pane(id, rect);
+        }
+    }
+
+    /// Move and resize the pane, or hide it when it is not shown
+    fn place_pane(&mut self, id: usize, rect: Option<Rect>) {
+        let Some(pane) = self.panes[id].as_ref() else {
+            return;
+        };
+
+        let (canvas, keyboard_control, size) = (pane.canvas, pane.keyboard_control, pane.size);
+
+        if let Some(cv) = self.control_mut(canvas) {
+            cv.set_visible(rect.is_some());
+        }
+
+        if let Some(control) = self.control_mut(keyboard_control) {
+            control.set_visible(rect.is_some());
+        }
+
+        let Some(rect) = rect else {
+            return;
+        };
+
+        let new_size = rect.size();
+
+        if let Some(control) = self.control_mut(keyboard_control) {
+            control.set_position(rect.left(), rect.top());
+            control.set_size(new_size.width as u16, new_size.height as u16);
+
+            if size != new_size {
+                control.tx
+                    .send_blocking(Input::Resize((
+                        new_size.width as usize,
+                        new_size.height as usize
+                    )))
+                    .ok();
+            }
+        }
+
+        if let Some(cv) = self.control_mut(canvas) {
+            cv.set_position(rect.left(), rect.top());
+            cv.set_size(new_size.width as u16, new_size.height as u16);
+            cv.resize_surface(new_size);
+        }
+
+        if size != new_size && let Some(pane) = self.panes[id].as_mut() {
+            pane.terminal_parser.resize(new_size.width, new_size.height);
+            pane.size = new_size;
+        }
+
+        self.render_pane(id);
+    }
+
+    /// Show the titles set by the applications in the tab bar
+    fn update_tab_bar(&mut self) {
+        let labels: Vec<String> = self.tabs
+            .iter()
+            .map(|tab| {
+                self.panes
+                    .get(tab.active_pane)
+                    .and_then(Option::as_ref)
+                    .and_then(|pane| pane.terminal_parser.title())
+                    .unwrap_or(&tab.name)
+                    .to_string()
+            })
+            .collect();
+
+        let active_tab = self.active_tab;
+        let tab_bar = self.tab_bar;
+
+        if let Some(bar) = self.control_mut(tab_bar) {
+            bar.labels = labels;
+            bar.active = active_tab;
+        }
+    }
+
+    /// Move and resize the window for the desktop, this is not reported as a move of the user
+    pub fn move_to(&mut self, rect: Rect) {
+        // An unchanged geometry is not reported, it would hide the next move of the user
+        if rect != Rect::with_point_and_size(self.position(), self.size()) {
+            self.expected_rect = Some(rect);
+        }
+
+        self.set_position(rect.left(), rect.top());
+        self.set_size(rect.width() as u16, rect.height() as u16);
+    }
+
+    /// The user moved the window and released it long enough ago
+    pub fn take_dropped(&mut self) -> bool {
+        match self.moved_at {
+            Some(moved_at) if moved_at.elapsed() >= DROP_DELAY => {
+                self.moved_at = None;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Cover the whole desktop, or go back to the previous geometry
+    pub fn toggle_maximize(&mut self, desktop_rect: Rect) {
+        let current = Rect::with_point_and_size(self.position(), self.size());
+        let Some(target) = maximize_target(current, desktop_rect, &mut self.restore_rect)
+        else {
+            return;
+        };
+
+        self.move_to(target);
+
+        // Resize the PTYs right away instead of waiting for the debounce
+        self.pending_resize = None;
+        self.layout_panes();
+    }
+
+    /// Only the panes of the active tab are rendered, the other ones are rendered when their tab is shown
+    fn render_pane(&mut self, id: usize) {
+        if !self.tab().pane_tree.contains(id) {
+            return;
+        }
+
+        let Some(pane) = self.panes[id].as_ref() else {
+            return;
+        };
+
+        let new_surface = pane.terminal_parser.render();
+        let canvas = pane.canvas;
+
+        if let Some(cv) = self.control_mut(canvas) {
+            *cv.drawing_surface_mut() = new_surface;
+        }
+    }
+
+    /// Keep track of the pane focused and of the tab clicked with the mouse
+    fn update_active_pane(&mut self) {
+        let tab_bar = self.tab_bar;
+        let clicked = self.control_mut(tab_bar).and_then(|bar| bar.clicked.take());
+
+        if let Some(tab) = clicked {
+            self.select_tab(tab);
+        }
+
+        let focused = (0..self.panes.len()).find(|id| {
+            self.panes[*id]
+                .as_ref()
+                .and_then(|pane| self.control(pane.keyboard_control))
+                .is_some_and(|control| control.has_focus())
+        });
+
+        if let Some(id) = focused && self.tab().pane_tree.contains(id) {
+            self.tab_mut().active_pane = id;
         }
+    }
+
+    /// Handle the next message of the application of the pane, background tabs keep their parser up to date
+    fn update_pane(&mut self, id: usize) -> EventProcessStatus {
+        let rx = self.panes[id]
+            .as_ref()
+            .and_then(|pane| self.control(pane.keyboard_control))
+            .map(|control| control.rx.clone());
 
-        match rx_clone.try_recv() {
+        let Some(rx) = rx else {
+            return EventProcessStatus::Ignored;
+        };
+
+        match rx.try_recv() {
             Ok(msg) => match msg {
-                Output::Pid(_) => EventProcessStatus::Ignored,
+                Output::Pid(pid) => {
+                    if let Some(pane) = self.panes[id].as_mut() {
+                        pane.pid = Some(pid);
+                    }
+
+                    EventProcessStatus::Ignored
+                }
                 Output::Stdout(command_output) => {
-                    let size = self.size();
-                    let inner_size = Size {
-                        width: size.width.saturating_sub(self.horizontal_adjustment),
-                        height: size.height.saturating_sub(self.vertical_adjustment),
+                    let Some(pane) = self.panes[id].as_mut() else {
+                        return EventProcessStatus::Ignored;
                     };
 
-                    let (old_surface, should_resize) = {
-                        let c = self.canvas;
-                        let cv = self.control_mut(c).unwrap();
+                    pane.terminal_parser.parse(&command_output);
 
-                        let should_resize = cv.size() != inner_size;
-                        let surface = cv.drawing_surface_mut();
+                    let input_modes = pane.terminal_parser.input_modes();
+                    let responses = pane.terminal_parser.take_responses();
+                    let keyboard_control = pane.keyboard_control;
 
-                        let mut buffer = Vec::new();
-                        surface.serialize_to_buffer(&mut buffer);
+                    self.render_pane(id);
 
-                        (Surface::from_buffer(&buffer).unwrap(), should_resize)
-                    };
+                    let control = self.control_mut(keyboard_control).unwrap();
+                    control.input_modes = input_modes;
 
-                    let new_surface = self.terminal_parser.parse_to_surface(&command_output, old_surface);
-
-                    let c = self.canvas;
-                    let cv = self.control_mut(c).unwrap();
-                    let surface = cv.drawing_surface_mut();
-                    *surface = new_surface;
-
-                    if should_resize {
-                        tx_clone
-                            .send_blocking(Input::Resize((
-                                inner_size.width as usize,
-                                inner_size.height as usize
-                            )))
-                            .ok();
-                        cv.set_size(inner_size.width as u16, inner_size.height as u16);
-                        cv.resize_surface(inner_size);
-                        self.terminal_parser.resize(inner_size.width, inner_size.height);
+                    if !responses.is_empty() {
+                        control.tx.send_blocking(Input::Data(responses)).ok();
                     }
 
                     EventProcessStatus::Processed
@@ -241,11 +943,11 @@ impl TimerEvents for TuiWindow {
                 Output::Error(error) => {
                     dialogs::error("An error occurred", &error);
 
-                    self.close();
+                    self.close_pane(id);
                     EventProcessStatus::Processed
                 },
                 Output::Terminated(_) => {
-                    self.close();
+                    self.close_pane(id);
                     EventProcessStatus::Processed
                 }
             }
@@ -254,32 +956,236 @@ impl TimerEvents for TuiWindow {
     }
 }
 
-fn replace_file_path(arg: String) -> anyhow::Result<String> {
-    match arg.contains("<FILE_PATH>") {
-        false => Ok(arg),
-        true => match dialogs::open(
-            "Select file",
-            "",
-            Location::Path(Path::new(env!("HOME"))),
-            None,
-            OpenFileDialogFlags::Icons | OpenFileDialogFlags::CheckIfFileExists
-        ) {
-            None => Err(anyhow!("No file selected")),
-            Some(file_path) => Ok(arg.replace("<FILE_PATH>", file_path.to_str().unwrap()))
+impl WindowEvents for TuiWindow {
+    /// AppCUI does not let windows overwrite `OnResize`, their size changes are reported as layout changes
+    fn on_layout_changed(&mut self, old_layout: Rect, new_layout: Rect) {
+        if old_layout.size() != new_layout.size() {
+            self.pending_resize = Some(Instant::now());
+        }
+
+        // AppCUI may adjust the geometry, so the next layout change is the desktop one even when it differs
+        if self.expected_rect.take().is_some() {
+            return;
+        }
+
+        if old_layout.size() == new_layout.size() && old_layout.top_left() != new_layout.top_left() {
+            self.moved_at = Some(Instant::now());
         }
     }
 }
 
-fn replace_folder_path(arg: String) -> anyhow::Result<String> {
-    match arg.contains("<FOLDER_PATH>") {
-        false => Ok(arg),
-        true => match dialogs::select_folder(
-            "Select folder",
-            Location::Path(Path::new(env!("HOME"))),
-            SelectFolderDialogFlags::Icons
-        ) {
-            None => Err(anyhow!("No folder selected")),
-            Some(file_path) => Ok(arg.replace("<FOLDER_PATH>", file_path.to_str().unwrap()))
+impl ToolBarEvents for TuiWindow {
+    /// Minimizing only hides the window, the desktop notices it and keeps the application running
+    fn on_button_clicked(&mut self, handle: Handle<toolbar::Button>) -> EventProcessStatus {
+        if handle == self.minimize_button {
+            self.set_visible(false);
+            return EventProcessStatus::Processed;
         }
+
+        EventProcessStatus::Ignored
     }
-}
\ No newline at end of file
+}
+
+impl TimerEvents for TuiWindow {
+    fn on_update(&mut self, _: u64) -> EventProcessStatus {
+        let mut status = EventProcessStatus::Ignored;
+
+        // Closing, every tab was closed or moved to another window
+        if self.tabs.is_empty() {
+            return status;
+        }
+
+        if resize_due(self.pending_resize, Instant::now()) {
+            self.pending_resize = None;
+            self.layout_panes();
+            status = EventProcessStatus::Processed;
+        }
+
+        self.update_active_pane();
+
+        for id in 0..self.panes.len() {
+            if self.update_pane(id) == EventProcessStatus::Processed {
+                status = EventProcessStatus::Processed;
+            }
+        }
+
+        if status == EventProcessStatus::Processed && self.tabs.len() > 1 {
+            self.update_tab_bar();
+        }
+
+        status
+    }
+}
+
+/// Whether the last size change is old enough for the terminal to be resized
+fn resize_due(pending_resize: Option<Instant>, now: Instant) -> bool {
+    pending_resize.is_some_and(|last_change| now.saturating_duration_since(last_change) >= RESIZE_DEBOUNCE)
+}
+
+/// Program, arguments and environment of a command, before it is started in a terminal
+#[derive(Debug, PartialEq, Eq)]
+struct PreparedCommand {
+    program: String,
+    args: Vec<String>,
+    /// Variables added to the environment of the terminal
+    env: Vec<(String, String)>,
+    term: String,
+    cwd: Option<String>,
+}
+
+/// Command starting the program in the working directory and environment of the launch options
+fn launch_command(program: &str, args: &[String], launch: &LaunchOptions) -> anyhow::Result<Command> {
+    let prepared = prepare_command(program, args, launch)?;
+
+    let mut cmd = Command::new(prepared.program)
+        .args(prepared.args)
+        .terminal_id(prepared.term);
+
+    for (key, value) in prepared.env {
+        cmd = cmd.env(key, value);
+    }
+
+    if let Some(cwd) = prepared.cwd {
+        cmd = cmd.current_dir(cwd);
+    }
+
+    Ok(cmd)
+}
+
+fn prepare_command(program: &str, args: &[String], launch: &LaunchOptions) -> anyhow::Result<PreparedCommand> {
+    let (program, args) = match launch.shell {
+        true => {
+            let shell = env::var("SHELL").unwrap_or("/bin/sh".to_string());
+            // The values of the placeholders are already quoted
+            let line = [program.to_string()].into_iter().chain(args.iter().cloned()).collect::<Vec<String>>().join(" ");
+            (shell, vec!["-c".to_string(), line])
+        }
+        false => (program.to_string(), args.to_vec()),
+    };
+
+    let mut variables = vec![("COLORTERM".to_string(), COLORTERM.to_string())];
+    variables.extend(launch.env.iter().map(|(key, value)| (key.clone(), expand_vars(value))));
+
+    // The terminal always sets TERM after the other variables
+    let term = variables.iter().rev().find(|(key, _)| key == "TERM").map(|(_, value)| value.clone()).unwrap_or(TERM.to_string());
+    variables.retain(|(key, _)| key != "TERM");
+
+    let cwd = match &launch.cwd {
+        Some(cwd) => {
+            let cwd = expand_vars(cwd);
+            if !Path::new(&cwd).is_dir() {
+                return Err(anyhow!("Working directory \"{cwd}\" does not exist"));
+            }
+
+            Some(cwd)
+        }
+        None => None,
+    };
+
+    let prepared = match launch.env_clear {
+        // The environment of the terminal cannot be cleared, env starts the program with only the given variables
+        true => {
+            let program_path = find_program(&program).ok_or(anyhow!("Command \"{program}\" was not found in $PATH"))?;
+
+            let env_args = ["-i".to_string(), format!("TERM={term}")]
+                .into_iter()
+                .chain(variables.iter().map(|(key, value)| format!("{key}={value}")))
+                .chain([program_path.to_string_lossy().to_string()])
+                .chain(args)
+                .collect();
+
+            PreparedCommand { program: "env".to_string(), args: env_args, env: Vec::new(), term, cwd }
+        }
+        false => PreparedCommand { program, args, env: variables, term, cwd },
+    };
+
+    Ok(prepared)
+}
+
+/// The geometry after toggling the maximization of a window at `current`, remembering the one to restore
+fn maximize_target(current: Rect, desktop_rect: Rect, restore_rect: &mut Option<Rect>) -> Option<Rect> {
+    match current == desktop_rect {
+        // Maximized with the title bar button, which keeps its own geometry
+        true => restore_rect.take(),
+        false => {
+            *restore_rect = Some(current);
+            Some(desktop_rect)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn debounces_resizes() {
+        let now = Instant::now();
+
+        assert!(!resize_due(None, now));
+        assert!(!resize_due(Some(now), now));
+        assert!(!resize_due(Some(now), now + RESIZE_DEBOUNCE - Duration::from_millis(1)));
+        assert!(resize_due(Some(now), now + RESIZE_DEBOUNCE));
+        assert!(!resize_due(Some(now + RESIZE_DEBOUNCE), now));
+    }
+
+    #[test]
+    fn toggles_maximization() {
+        let desktop = Rect::new(0, 1, 99, 29);
+        let window = Rect::new(10, 5, 49, 19);
+
+        // (current, remembered geometry, expected target, remembered geometry afterwards)
+        let cases = [
+            (window, None, Some(desktop), Some(window)),
+            (window, Some(Rect::new(0, 0, 9, 9)), Some(desktop), Some(window)),
+            (desktop, Some(window), Some(window), None),
+            (desktop, None, None, None),
+        ];
+
+        for (current, restore_rect, expected_target, expected_restore_rect) in cases {
+            let mut restore_rect = restore_rect;
+            assert_eq!(maximize_target(current, desktop, &mut restore_rect), expected_target, "{current:?}");
+            assert_eq!(restore_rect, expected_restore_rect, "{current:?}");
+        }
+    }
+
+    #[test]
+    fn prepares_commands() {
+        let strings = |values: &[&str]| values.iter().map(|value| value.to_string()).collect::<Vec<String>>();
+        let variables = |values: &[(&str, &str)]| values.iter().map(|(key, value)| (key.to_string(), value.to_string())).collect::<Vec<(String, String)>>();
+        let sh = find_program("sh").unwrap().to_string_lossy().to_string();
+        let args = strings(&["-c", "echo $A"]);
+
+        let launch = LaunchOptions {
+            env: [("A", "1"), ("TERM", "vt100")].into_iter().map(|(key, value)| (key.to_string(), value.to_string())).collect(),
+            cwd: Some("/".to_string()),
+            ..LaunchOptions::default()
+        };
+
+        assert_eq!(prepare_command("sh", &args, &launch).unwrap(), PreparedCommand {
+            program: "sh".to_string(),
+            args: args.clone(),
+            env: variables(&[("COLORTERM", COLORTERM), ("A", "1")]),
+            term: "vt100".to_string(),
+            cwd: Some("/".to_string()),
+        });
+
+        // env starts the program with only the variables of the shortcut and the terminal
+        let cleared = LaunchOptions { env_clear: true, cwd: None, ..launch.clone() };
+        assert_eq!(prepare_command("sh", &args, &cleared).unwrap(), PreparedCommand {
+            program: "env".to_string(),
+            args: strings(&["-i", "TERM=vt100", "COLORTERM=truecolor", "A=1", &sh, "-c", "echo $A"]),
+            env: Vec::new(),
+            term: "vt100".to_string(),
+            cwd: None,
+        });
+
+        let default_term = LaunchOptions { env_clear: true, ..LaunchOptions::default() };
+        assert_eq!(prepare_command("sh", &[], &default_term).unwrap().args, strings(&["-i", &format!("TERM={TERM}"), "COLORTERM=truecolor", &sh]));
+
+        assert!(prepare_command("desktop-tui-missing-program", &[], &cleared).is_err());
+
+        let missing_cwd = LaunchOptions { cwd: Some("/desktop-tui-missing-folder".to_string()), ..LaunchOptions::default() };
+        assert!(prepare_command("sh", &[], &missing_cwd).is_err());
+    }
+}
@@ -1,6 +1,184 @@
 use chrono::Local;
+use std::env;
+use std::path::{Path, PathBuf};
 
 pub fn time_to_string() -> String {
     let now = Local::now();
     now.format("%H:%M ").to_string()
-}
\ No newline at end of file
+}
+/// Path of the program, searched in `$PATH` unless it contains a slash
+pub fn find_program(program: &str) -> Option<PathBuf> {
+    if program.contains('/') {
+        return Path::new(program).is_file().then(|| PathBuf::from(program));
+    }
+
+    env::split_paths(&env::var_os("PATH")?)
+        .map(|dir| dir.join(program))
+        .find(|path| path.is_file())
+}
+
+/// `$HOME` of the user running the desktop, or the root directory
+pub fn home_dir() -> PathBuf {
+    env::var_os("HOME").filter(|home| !home.is_empty()).map(PathBuf::from).unwrap_or(PathBuf::from("/"))
+}
+
+/// Replace a leading `~` by the home directory, and `$VAR` or `${VAR}` by the value of the variable, empty when it is not set
+pub fn expand_vars(text: &str) -> String {
+    expand_vars_with(text, |name| env::var(name).ok())
+}
+
+/// `expand_vars` with the variables found by `lookup`
+fn expand_vars_with(text: &str, lookup: impl Fn(&str) -> Option<String>) -> String {
+    let text = match (text.strip_prefix('~'), lookup("HOME")) {
+        (Some(rest), Some(home)) if rest.is_empty() || rest.starts_with('/') => format!("{home}{rest}"),
+        _ => text.to_string(),
+    };
+
+    let mut expanded = String::new();
+    let mut characters = text.chars().peekable();
+
+    while let Some(character) = characters.next() {
+        if character != '$' {
+            expanded.push(character);
+            continue;
+        }
+
+        let name: String = match characters.next_if_eq(&'{') {
+            Some(_) => characters.by_ref().take_while(|character| *character != '}').collect(),
+            None => std::iter::from_fn(|| characters.next_if(|character| character.is_ascii_alphanumeric() || *character == '_')).collect(),
+        };
+
+        match name.is_empty() {
+            true => expanded.push('$'),
+            false => expanded.push_str(&lookup(&name).unwrap_or_default()),
+        }
+    }
+
+    expanded
+}
+
+/// Word read literally by a POSIX shell
+pub fn shell_quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "'\\''"))
+}
+
+/// Candidate a misspelled word most likely stands for
+pub fn closest_match<'a>(word: &str, candidates: &[&'a str]) -> Option<&'a str> {
+    // "commands" stands for "additional_commands"
+    candidates
+        .iter()
+        .filter_map(|candidate| {
+            let distance = candidate
+                .split('_')
+                .chain([*candidate])
+                .map(|part| edit_distance(word, part))
+                .min()?;
+
+            (distance <= word.len().max(3) / 3).then_some((*candidate, distance))
+        })
+        .min_by_key(|(_, distance)| *distance)
+        .map(|(candidate, _)| candidate)
+}
+
+/// Levenshtein distance
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != *b_char);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expands_variables() {
+        let lookup = |name: &str| match name {
+            "HOME" => Some("/home/me".to_string()),
+            "EDITOR" => Some("hx".to_string()),
+            "EMPTY" => Some(String::new()),
+            _ => None,
+        };
+
+        let cases = [
+            ("~", "/home/me"),
+            ("~/src", "/home/me/src"),
+            ("~user/src", "~user/src"),
+            ("a/~", "a/~"),
+            ("$EDITOR", "hx"),
+            ("$EDITOR.toml", "hx.toml"),
+            ("${EDITOR}s", "hxs"),
+            ("$EDITORS", ""),
+            ("~/$EDITOR/${EDITOR}", "/home/me/hx/hx"),
+            ("a${UNSET}b$UNSET", "ab"),
+            ("a${EMPTY}b", "ab"),
+            ("$", "$"),
+            ("5$ or $-", "5$ or $-"),
+            ("$$EDITOR", "$hx"),
+            ("${EDITOR", "hx"),
+            ("", ""),
+        ];
+
+        for (text, expected) in cases {
+            assert_eq!(expand_vars_with(text, lookup), expected, "{text}");
+        }
+
+        assert_eq!(expand_vars_with("~/src", |_| None), "~/src");
+    }
+
+    #[test]
+    fn measures_edit_distances() {
+        let cases = [
+            ("", "", 0),
+            ("abc", "", 3),
+            ("", "ab", 2),
+            ("command", "command", 0),
+            ("comand", "command", 1),
+            ("kitten", "sitting", 3),
+            ("flaw", "lawn", 2),
+            ("é", "e", 1),
+        ];
+
+        for (a, b, expected) in cases {
+            assert_eq!(edit_distance(a, b), expected, "{a} {b}");
+            assert_eq!(edit_distance(b, a), expected, "{b} {a}");
+        }
+    }
+
+    #[test]
+    fn finds_closest_matches() {
+        let fields = ["name", "command", "args", "additional_commands"];
+
+        let cases = [
+            ("comand", Some("command")),
+            ("argss", Some("args")),
+            ("nme", Some("name")),
+            // Matched with a part of the field
+            ("commands", Some("additional_commands")),
+            ("additional", Some("additional_commands")),
+            // A third of the length, and at least one edit, is the largest distance
+            ("xxmmand", Some("command")),
+            ("xxxmand", None),
+            ("nmx", None),
+            ("position", None),
+        ];
+
+        for (word, expected) in cases {
+            assert_eq!(closest_match(word, &fields), expected, "{word}");
+        }
+
+        assert_eq!(closest_match("name", &[]), None);
+    }
+}
@@ -0,0 +1,76 @@
+use async_channel::Sender;
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::path::{Path, PathBuf};
+
+/// Send a message when a file of the shortcut directories or the config file changes, unless one is already waiting in the bounded channel.
+/// The watcher stops when it is dropped
+pub fn watch_files(shortcut_dirs: &[PathBuf], config_path: Option<PathBuf>, changes: Sender<()>) -> anyhow::Result<RecommendedWatcher> {
+    // Event paths are absolute
+    let watched_dirs: Vec<PathBuf> = shortcut_dirs.iter().filter_map(|shortcut_dir| shortcut_dir.canonicalize().ok()).collect();
+    let watched_config = config_path.map(|config_path| config_path.canonicalize().unwrap_or(config_path));
+
+    let (event_dirs, event_config) = (watched_dirs.clone(), watched_config.clone());
+
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+        let Ok(event) = event else {
+            return;
+        };
+
+        if matches!(event.kind, EventKind::Access(_)) {
+            return;
+        }
+
+        // A burst of events is coalesced, the desktop waits for the changes to settle before reloading
+        if event.paths.iter().any(|path| is_watched(path, &event_dirs, event_config.as_deref())) {
+            changes.try_send(()).ok();
+        }
+    })?;
+
+    for watched_dir in &watched_dirs {
+        watcher.watch(watched_dir, RecursiveMode::Recursive)?;
+    }
+
+    // Watching the directory also catches a config file created or replaced by an editor
+    if let Some(config_dir) = watched_config.as_ref().and_then(|config_path| config_path.parent()).filter(|config_dir| config_dir.is_dir())
+        && !watched_dirs.iter().any(|watched_dir| config_dir.starts_with(watched_dir)) {
+        watcher.watch(config_dir, RecursiveMode::NonRecursive)?;
+    }
+
+    Ok(watcher)
+}
+
+/// TOML file of a shortcut directory, or the config file
+fn is_watched(path: &Path, watched_dirs: &[PathBuf], watched_config: Option<&Path>) -> bool {
+    path.extension().is_some_and(|extension| extension.to_string_lossy().ends_with("toml"))
+        && (watched_config == Some(path) || watched_dirs.iter().any(|watched_dir| path.starts_with(watched_dir)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filters_paths() {
+        let watched_dirs = [PathBuf::from("/etc/xdg/desktop-tui/shortcuts"), PathBuf::from("/home/user/shortcuts")];
+        let watched_config = Some(Path::new("/home/user/.config/desktop-tui/config.toml"));
+
+        let cases = [
+            ("/home/user/shortcuts/editor.toml", true),
+            ("/home/user/shortcuts/tools/htop.toml", true),
+            ("/etc/xdg/desktop-tui/shortcuts/terminal.toml", true),
+            ("/home/user/.config/desktop-tui/config.toml", true),
+            ("/home/user/shortcuts/notes.txt", false),
+            ("/home/user/shortcuts/.editor.toml.swp", false),
+            ("/home/user/shortcuts", false),
+            ("/home/user/shortcuts-old/editor.toml", false),
+            ("/home/user/.config/desktop-tui/other.toml", false),
+            ("/tmp/editor.toml", false),
+        ];
+
+        for (path, expected) in cases {
+            assert_eq!(is_watched(Path::new(path), &watched_dirs, watched_config), expected, "{path}");
+        }
+
+        assert!(!is_watched(Path::new("/home/user/.config/desktop-tui/config.toml"), &watched_dirs, None));
+    }
+}
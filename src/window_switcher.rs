@@ -0,0 +1,184 @@
+use appcui::graphics::LineType;
+use appcui::input::{Key, KeyCode, KeyModifier};
+use appcui::prelude::window::Flags;
+use appcui::prelude::*;
+use std::time::{Duration, Instant};
+
+/// Time without input after which the switcher closes without changing the focus.
+/// AppCUI does not report the modifier release, so the selection is only focused with Enter
+const SWITCHER_TIMEOUT: Duration = Duration::from_secs(10);
+const SWITCHER_WIDTH: u32 = 60;
+/// Size of the terminal text shown below the list
+pub const PREVIEW_WIDTH: usize = SWITCHER_WIDTH as usize - 4;
+pub const PREVIEW_HEIGHT: usize = 8;
+
+#[derive(Clone, Debug)]
+pub struct SwitcherEntry {
+    pub app_name: String,
+    /// Title set by the application
+    pub title: Option<String>,
+    /// Last lines of the terminal
+    pub preview: Vec<String>,
+}
+
+#[CustomControl(overwrite = OnPaint + OnKeyPressed)]
+pub struct SwitcherList {
+    pub entries: Vec<SwitcherEntry>,
+    pub selected: usize,
+    pub cycle_key: Key,
+    pub last_input: Instant,
+}
+
+#[ModalWindow(events = TimerEvents + WindowEvents, response = usize)]
+pub struct WindowSwitcher {
+    pub list: Handle<SwitcherList>,
+}
+
+impl WindowSwitcher {
+    /// Entries are in most-recently-used order, the second one is selected first
+    pub fn new(entries: Vec<SwitcherEntry>, cycle_key: Key) -> Self {
+        let height = (entries.len() + 1 + PREVIEW_HEIGHT + 2) as u32;
+        let selected = 1.min(entries.len().saturating_sub(1));
+
+        let mut switcher = Self {
+            base: ModalWindow::new(
+                "Windows",
+                LayoutBuilder::new()
+                    .alignment(Alignment::Center)
+                    .width(SWITCHER_WIDTH)
+                    .height(height)
+                    .build(),
+                Flags::None
+            ),
+            list: Handle::None,
+        };
+
+        switcher.list = switcher.add(SwitcherList {
+            base: ControlBase::new(Layout::fill(), true),
+            entries,
+            selected,
+            cycle_key,
+            last_input: Instant::now(),
+        });
+
+        if let Some(timer) = switcher.timer() {
+            timer.start(Duration::from_millis(100));
+        }
+
+        switcher
+    }
+
+    fn selected(&self) -> usize {
+        self.control(self.list).map_or(0, |list| list.selected)
+    }
+}
+
+impl TimerEvents for WindowSwitcher {
+    fn on_update(&mut self, _: u64) -> EventProcessStatus {
+        let timed_out = self.control(self.list).is_some_and(|list| list.last_input.elapsed() >= SWITCHER_TIMEOUT);
+
+        if timed_out {
+            self.exit();
+            return EventProcessStatus::Processed;
+        }
+
+        EventProcessStatus::Ignored
+    }
+}
+
+impl WindowEvents for WindowSwitcher {
+    fn on_accept(&mut self) {
+        let selected = self.selected();
+        self.exit_with(selected);
+    }
+}
+
+impl OnKeyPressed for SwitcherList {
+    fn on_key_pressed(&mut self, key: Key, _character: char) -> EventProcessStatus {
+        let Some(selected) = cycled(self.selected, self.entries.len(), key, self.cycle_key) else {
+            return EventProcessStatus::Ignored;
+        };
+
+        self.selected = selected;
+        self.last_input = Instant::now();
+
+        EventProcessStatus::Processed
+    }
+}
+
+/// Selection after the key, none when the key does not move it
+fn cycled(selected: usize, count: usize, key: Key, cycle_key: Key) -> Option<usize> {
+    if count == 0 {
+        return None;
+    }
+
+    let backward = Key::new(cycle_key.code, cycle_key.modifier | KeyModifier::Shift);
+
+    if key == cycle_key || key.code == KeyCode::Down || key == Key::new(KeyCode::Tab, KeyModifier::None) {
+        Some((selected + 1) % count)
+    }
+    else if key == backward || key.code == KeyCode::Up || key == Key::new(KeyCode::Tab, KeyModifier::Shift) {
+        Some((selected + count - 1) % count)
+    }
+    else {
+        None
+    }
+}
+
+impl OnPaint for SwitcherList {
+    fn on_paint(&self, surface: &mut Surface, theme: &Theme) {
+        let width = self.size().width;
+
+        for (index, entry) in self.entries.iter().enumerate() {
+            let y = index as i32;
+            let attribute = match index == self.selected {
+                true => theme.list_current_item.focus,
+                false => theme.text.normal,
+            };
+
+            let label = match &entry.title {
+                Some(title) => format!("{} - {}", entry.app_name, title),
+                None => entry.app_name.clone(),
+            };
+
+            surface.fill_horizontal_line_with_size(0, y, width, Character::with_attributes(' ', attribute));
+            surface.write_string(1, y, &label, attribute, false);
+        }
+
+        let separator_y = self.entries.len() as i32;
+        surface.draw_horizontal_line_with_size(0, separator_y, width, LineType::Single, theme.lines.normal);
+
+        if let Some(entry) = self.entries.get(self.selected) {
+            for (index, line) in entry.preview.iter().enumerate() {
+                surface.write_string(1, separator_y + 1 + index as i32, line, theme.text.inactive, false);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cycles_selection() {
+        let cycle_key = Key::new(KeyCode::W, KeyModifier::Ctrl | KeyModifier::Alt);
+        let cases = [
+            (0, 3, cycle_key, Some(1)),
+            (2, 3, cycle_key, Some(0)),
+            (0, 3, Key::new(KeyCode::W, KeyModifier::Ctrl | KeyModifier::Alt | KeyModifier::Shift), Some(2)),
+            (1, 3, Key::new(KeyCode::Down, KeyModifier::None), Some(2)),
+            (0, 3, Key::new(KeyCode::Up, KeyModifier::None), Some(2)),
+            (1, 3, Key::new(KeyCode::Tab, KeyModifier::None), Some(2)),
+            (1, 3, Key::new(KeyCode::Tab, KeyModifier::Shift), Some(0)),
+            (0, 1, cycle_key, Some(0)),
+            (0, 0, cycle_key, None),
+            (0, 3, Key::new(KeyCode::W, KeyModifier::None), None),
+            (0, 3, Key::new(KeyCode::Enter, KeyModifier::None), None),
+        ];
+
+        for (selected, count, key, expected) in cases {
+            assert_eq!(cycled(selected, count, key, cycle_key), expected, "{selected} {count} {key:?}");
+        }
+    }
+}